@@ -0,0 +1,142 @@
+use std::fs;
+use std::io::{self, BufRead};
+use std::thread;
+use std::time::Duration;
+
+use clap::Parser;
+use crossbeam_channel as channel;
+use elevators::config;
+use elevators::elevator::elevio_server::{parse_control_line, ControlCommand, ElevioServer};
+use elevators::elevator::{SimulatedElevator, SimulatorConfig};
+use log::{error, info, LevelFilter};
+
+// Simulated elevator served over the elevio TCP protocol, in place of the elevator server
+// Control lines are read from the console, and optionally from a script at startup.
+#[derive(Debug, Parser)]
+#[command(version, about = "Simulated elevator speaking the elevio protocol")]
+struct Cli {
+    #[arg(
+        long,
+        default_value = "config.toml",
+        help = "Configuration file providing the port and number of floors"
+    )]
+    config: String,
+
+    #[arg(
+        long,
+        env = "ELEVATOR_PROFILE",
+        help = "Configuration profile layered on top of the file, e.g. dev or prod"
+    )]
+    profile: Option<String>,
+
+    #[arg(long, default_value = "0.0.0.0", help = "Address to listen on")]
+    address: String,
+
+    #[arg(
+        long,
+        help = "Port to listen on, overrides the driver port in the configuration"
+    )]
+    port: Option<u32>,
+
+    #[arg(long, help = "Number of floors, overrides the configuration")]
+    num_floors: Option<u8>,
+
+    #[arg(
+        long,
+        default_value_t = 2000,
+        help = "Time in milliseconds to travel between two adjacent floors"
+    )]
+    travel_time_milliseconds: u64,
+
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        help = "Start position in floors, a fraction starts between floors"
+    )]
+    start_position: f64,
+
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        help = "Virtual seconds passing per real second"
+    )]
+    time_scale: f64,
+
+    #[arg(long, help = "File with control lines to schedule at startup")]
+    script: Option<String>,
+
+    #[arg(
+        long,
+        help = "Log level, overrides RUST_LOG (off, error, warn, info, debug, trace)"
+    )]
+    log_level: Option<LevelFilter>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    let mut logger = env_logger::Builder::from_default_env();
+    logger.filter_level(cli.log_level.unwrap_or(LevelFilter::Info));
+    logger.init();
+
+    let config = config::load(&cli.config, cli.profile.as_deref())?;
+    let port = cli.port.unwrap_or(config.hardware.driver_port);
+    let num_floors = cli.num_floors.unwrap_or(config.hardware.num_floors);
+
+    let elevator = SimulatedElevator::new(SimulatorConfig {
+        num_floors,
+        travel_time: Duration::from_millis(cli.travel_time_milliseconds),
+        start_position: cli.start_position,
+        time_scale: cli.time_scale,
+        ..SimulatorConfig::default()
+    });
+
+    if let Some(script) = &cli.script {
+        let source = fs::read_to_string(script)?;
+        for (number, line) in source.lines().enumerate() {
+            match parse_control_line(line) {
+                Ok(Some(command)) => apply(&elevator, command),
+                Ok(None) => {}
+                Err(message) => {
+                    return Err(format!("{}:{}: {}", script, number + 1, message).into());
+                }
+            }
+        }
+        info!("Scheduled script {}", script);
+    }
+
+    let (_terminate_tx, terminate_rx) = channel::unbounded::<()>();
+    let server = ElevioServer::bind(
+        &format!("{}:{}", cli.address, port),
+        elevator.clone(),
+        terminate_rx,
+    )?;
+    let server_thread = thread::spawn(move || server.run());
+
+    info!(
+        "Simulating {} floors, type commands to control the elevator",
+        num_floors
+    );
+    for line in io::stdin().lock().lines() {
+        match parse_control_line(&line?) {
+            Ok(Some(command)) => apply(&elevator, command),
+            Ok(None) => {}
+            Err(message) => error!("{}", message),
+        }
+    }
+
+    // keep serving without a console, e.g. when detached in a container
+    let _ = server_thread.join();
+    Ok(())
+}
+
+fn apply(elevator: &SimulatedElevator, command: ControlCommand) {
+    match command {
+        ControlCommand::Events(events) => {
+            for (delay, event) in events {
+                elevator.schedule(delay, event);
+            }
+        }
+        ControlCommand::Status => println!("{}", elevator),
+    }
+}
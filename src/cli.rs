@@ -0,0 +1,138 @@
+use clap::Parser;
+use log::LevelFilter;
+use uhlc::{SizeError, ID};
+
+use crate::config::Config;
+
+// Node ID used when none is given on the command line
+const DEFAULT_NODE_ID: [u8; 3] = [0x01, 0x02, 0x03];
+
+// Command line options, these take precedence over the configuration files and environment
+#[derive(Debug, Parser)]
+#[command(version, about = "Distributed elevator controller")]
+pub struct Cli {
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Unique ID of this node, must differ between all nodes in the network"
+    )]
+    pub id: Option<u64>,
+
+    #[arg(
+        long,
+        default_value = "config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[arg(
+        long,
+        env = "ELEVATOR_PROFILE",
+        help = "Configuration profile layered on top of the file, e.g. dev or prod"
+    )]
+    pub profile: Option<String>,
+
+    #[arg(long, help = "Port of the elevator server")]
+    pub driver_port: Option<u32>,
+
+    #[arg(long, help = "Port shared by all nodes for network traffic")]
+    pub network_port: Option<u32>,
+
+    #[arg(long, help = "Number of floors served by the elevator")]
+    pub num_floors: Option<u8>,
+
+    #[arg(
+        long,
+        help = "Log level, overrides RUST_LOG (off, error, warn, info, debug, trace)"
+    )]
+    pub log_level: Option<LevelFilter>,
+}
+
+impl Cli {
+    // ID for the node and its clock
+    pub fn node_id(&self) -> Result<ID, SizeError> {
+        match self.id {
+            Some(id) => ID::try_from(id),
+            None => ID::try_from(DEFAULT_NODE_ID),
+        }
+    }
+
+    // Override the configuration with the values given on the command line
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(driver_port) = self.driver_port {
+            config.hardware.driver_port = driver_port;
+        }
+        if let Some(network_port) = self.network_port {
+            config.network.port = network_port;
+        }
+        if let Some(num_floors) = self.num_floors {
+            config.hardware.num_floors = num_floors;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let cli = Cli::try_parse_from(["elevators"]).unwrap();
+        assert_eq!(cli.config, "config.toml");
+        assert_eq!(
+            cli.node_id().unwrap(),
+            ID::try_from(DEFAULT_NODE_ID).unwrap()
+        );
+        assert!(cli.log_level.is_none());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let cli = Cli::try_parse_from([
+            "elevators",
+            "--id",
+            "2",
+            "--config",
+            "config-dev.toml",
+            "--profile",
+            "prod",
+            "--driver-port",
+            "15658",
+            "--network-port",
+            "4321",
+            "--num-floors",
+            "6",
+            "--log-level",
+            "debug",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.node_id().unwrap(), ID::try_from(2u64).unwrap());
+        assert_eq!(cli.config, "config-dev.toml");
+        assert_eq!(cli.profile.as_deref(), Some("prod"));
+        assert_eq!(cli.driver_port, Some(15658));
+        assert_eq!(cli.network_port, Some(4321));
+        assert_eq!(cli.num_floors, Some(6));
+        assert_eq!(cli.log_level, Some(LevelFilter::Debug));
+    }
+
+    #[test]
+    fn test_apply_overrides_config() {
+        let cli = Cli::try_parse_from(["elevators", "--network-port", "4321", "--num-floors", "6"])
+            .unwrap();
+        let mut config = crate::config::load("config.toml", None).unwrap();
+        let driver_port = config.hardware.driver_port;
+
+        cli.apply(&mut config);
+        assert_eq!(config.network.port, 4321);
+        assert_eq!(config.hardware.num_floors, 6);
+        assert_eq!(config.hardware.driver_port, driver_port);
+    }
+
+    #[test]
+    fn test_invalid_arguments_are_rejected() {
+        assert!(Cli::try_parse_from(["elevators", "--id", "0"]).is_err());
+        assert!(Cli::try_parse_from(["elevators", "--num-floors", "300"]).is_err());
+        assert!(Cli::try_parse_from(["elevators", "--log-level", "loud"]).is_err());
+    }
+}
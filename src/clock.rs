@@ -1,6 +1,7 @@
 use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 use uhlc::{HLCBuilder, Timestamp, HLC, ID, NTP64};
+use uuid::Uuid;
 
 pub trait TimestampExt {
     fn add_millis(&self, millis: u64) -> Self;
@@ -64,6 +65,12 @@ pub fn get_clock_id() -> &'static ID {
     get_clock().get_id()
 }
 
+// Get the ID of the global clock as a UUID
+// Orders and scheduler contexts identify elevators by UUID
+pub fn get_clock_uuid() -> Uuid {
+    Uuid::from_bytes(get_clock_id().to_le_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,61 +1,552 @@
 use serde::Deserialize;
 use std::fmt;
 use std::fs;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use toml::{Table, Value};
 
-#[derive(Debug, Deserialize)]
+use crate::queue::scheduler;
+
+// Prefix of the environment variables that override configuration values
+// Variables are named after the section and field, e.g. ELEVATOR_HARDWARE_NUM_FLOORS.
+pub const ENV_PREFIX: &str = "ELEVATOR_";
+const SECTIONS: [&str; 4] = ["hardware", "network", "storage", "scheduling"];
+
+// Every field has a built-in default, so configuration files only need the values they change
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub hardware: HardwareConfig,
     pub network: NetworkConfig,
+    pub storage: StorageConfig,
+    pub scheduling: SchedulingConfig,
 }
 
 impl fmt::Display for Config {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Config:\n{}\n{}", self.hardware, self.network)
+        write!(
+            f,
+            "Config:\n{}\n{}\n{}\n{}",
+            self.hardware, self.network, self.storage, self.scheduling
+        )
     }
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct HardwareConfig {
     pub num_floors: u8,
     pub driver_address: String,
     pub driver_port: u32,
     pub driver_channel_poll_timeout_milliseconds: u64,
+    pub button_debounce_milliseconds: u64, // how long a button reading must hold, 0 disables
+    pub door_open_duration_milliseconds: u64,
+    pub door_stuck_timeout_milliseconds: u64,
+    pub homing_timeout_milliseconds: u64,
+    pub halt_policy: HaltPolicy,
+    pub order_expiry_seconds: u64,
+}
+
+impl Default for HardwareConfig {
+    fn default() -> Self {
+        Self {
+            num_floors: 4,
+            driver_address: "localhost".to_string(),
+            driver_port: 15657,
+            driver_channel_poll_timeout_milliseconds: 10,
+            button_debounce_milliseconds: 20,
+            door_open_duration_milliseconds: 3000,
+            door_stuck_timeout_milliseconds: 20000,
+            homing_timeout_milliseconds: 10000,
+            halt_policy: HaltPolicy::default(),
+            order_expiry_seconds: 60,
+        }
+    }
 }
 
 impl fmt::Display for HardwareConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Hardware Config:\n  Floors: {}\n  Driver: {}:{}\n  Poll Timeout: {}ms",
+            "Hardware Config:\n  Floors: {}\n  Driver: {}:{}\n  Poll Timeout: {}ms\n  Button Debounce: {}ms\n  Door Open: {}ms\n  Door Stuck Timeout: {}ms\n  Homing Timeout: {}ms\n  Halt Policy: {}\n  Order Expiry: {}s",
             self.num_floors,
             self.driver_address,
             self.driver_port,
-            self.driver_channel_poll_timeout_milliseconds
+            self.driver_channel_poll_timeout_milliseconds,
+            self.button_debounce_milliseconds,
+            self.door_open_duration_milliseconds,
+            self.door_stuck_timeout_milliseconds,
+            self.homing_timeout_milliseconds,
+            self.halt_policy,
+            self.order_expiry_seconds
         )
     }
 }
 
+// What the elevator does when the stop button is pressed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HaltPolicy {
+    // Stop the motor immediately and keep all orders
+    #[default]
+    StopAndKeepOrders,
+    // Stop the motor immediately and drop the cab orders
+    StopAndClearCab,
+    // Continue to the nearest floor in the direction of travel and open the doors
+    NearestFloorOpenDoors,
+}
+
+impl fmt::Display for HaltPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HaltPolicy::StopAndKeepOrders => write!(f, "stop and keep orders"),
+            HaltPolicy::StopAndClearCab => write!(f, "stop and clear cab orders"),
+            HaltPolicy::NearestFloorOpenDoors => write!(f, "nearest floor and open doors"),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct NetworkConfig {
-    pub address: String,
+    pub address: String, // broadcast address shared by all nodes
     pub port: u32,
+    pub heartbeat_interval_milliseconds: u64,
+    pub peer_timeout_milliseconds: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            address: "255.255.255.255".to_string(),
+            port: 1234,
+            heartbeat_interval_milliseconds: 100,
+            peer_timeout_milliseconds: 1000,
+        }
+    }
 }
 
 impl fmt::Display for NetworkConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Network Config:\n  Address: {}:{}",
-            self.address, self.port
+            "Network Config:\n  Address: {}:{}\n  Heartbeat Interval: {}ms\n  Peer Timeout: {}ms",
+            self.address,
+            self.port,
+            self.heartbeat_interval_milliseconds,
+            self.peer_timeout_milliseconds
         )
     }
 }
 
-pub fn load() -> Config {
-    let config_string = fs::read_to_string("config.toml").expect("Failed to load config file");
-    toml::from_str(&config_string).expect("Failed to parse configuration from file")
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub journal_path: String, // cab order journal, must survive restarts
+    pub journal_compaction_threshold: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            journal_path: "data/cab_orders.journal".to_string(),
+            journal_compaction_threshold: 1000,
+        }
+    }
+}
+
+impl fmt::Display for StorageConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Storage Config:\n  Journal: {}\n  Journal Compaction Threshold: {} records",
+            self.journal_path, self.journal_compaction_threshold
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SchedulingConfig {
+    pub scheduler: String, // name in the scheduler registry
+}
+
+impl Default for SchedulingConfig {
+    fn default() -> Self {
+        Self {
+            scheduler: "collective".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SchedulingConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scheduling Config:\n  Scheduler: {}", self.scheduler)
+    }
+}
+
+// Values that can be changed while the elevator is running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tunables {
+    pub driver_channel_poll_timeout_milliseconds: u64,
+    pub button_debounce_milliseconds: u64,
+    pub door_open_duration_milliseconds: u64,
+    pub door_stuck_timeout_milliseconds: u64,
+    pub halt_policy: HaltPolicy,
+    pub order_expiry_seconds: u64,
+    pub scheduler: String,
+}
+
+impl Config {
+    pub fn tunables(&self) -> Tunables {
+        Tunables {
+            driver_channel_poll_timeout_milliseconds: self
+                .hardware
+                .driver_channel_poll_timeout_milliseconds,
+            button_debounce_milliseconds: self.hardware.button_debounce_milliseconds,
+            door_open_duration_milliseconds: self.hardware.door_open_duration_milliseconds,
+            door_stuck_timeout_milliseconds: self.hardware.door_stuck_timeout_milliseconds,
+            halt_policy: self.hardware.halt_policy,
+            order_expiry_seconds: self.hardware.order_expiry_seconds,
+            scheduler: self.scheduling.scheduler.clone(),
+        }
+    }
+
+    // Fields that differ from the other configuration but need a restart to change
+    pub fn structural_changes(&self, other: &Config) -> Vec<&'static str> {
+        let (hardware, other_hardware) = (&self.hardware, &other.hardware);
+        let (network, other_network) = (&self.network, &other.network);
+        let (storage, other_storage) = (&self.storage, &other.storage);
+
+        [
+            (
+                "hardware.num_floors",
+                hardware.num_floors != other_hardware.num_floors,
+            ),
+            (
+                "hardware.driver_address",
+                hardware.driver_address != other_hardware.driver_address,
+            ),
+            (
+                "hardware.driver_port",
+                hardware.driver_port != other_hardware.driver_port,
+            ),
+            (
+                "hardware.homing_timeout_milliseconds",
+                hardware.homing_timeout_milliseconds != other_hardware.homing_timeout_milliseconds,
+            ),
+            ("network.address", network.address != other_network.address),
+            ("network.port", network.port != other_network.port),
+            (
+                "network.heartbeat_interval_milliseconds",
+                network.heartbeat_interval_milliseconds
+                    != other_network.heartbeat_interval_milliseconds,
+            ),
+            (
+                "network.peer_timeout_milliseconds",
+                network.peer_timeout_milliseconds != other_network.peer_timeout_milliseconds,
+            ),
+            (
+                "storage.journal_path",
+                storage.journal_path != other_storage.journal_path,
+            ),
+            (
+                "storage.journal_compaction_threshold",
+                storage.journal_compaction_threshold != other_storage.journal_compaction_threshold,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(field, is_changed)| is_changed.then_some(field))
+        .collect()
+    }
+
+    // Check the values for problems the parser cannot catch, every problem is reported
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let hardware = &self.hardware;
+        let network = &self.network;
+        let storage = &self.storage;
+
+        if hardware.num_floors < 2 {
+            problems.push(format!(
+                "hardware.num_floors must be at least 2, got {}",
+                hardware.num_floors
+            ));
+        }
+        check_port(&mut problems, "hardware.driver_port", hardware.driver_port);
+        check_port(&mut problems, "network.port", network.port);
+
+        for (field, value) in [
+            (
+                "hardware.driver_channel_poll_timeout_milliseconds",
+                hardware.driver_channel_poll_timeout_milliseconds,
+            ),
+            (
+                "hardware.door_open_duration_milliseconds",
+                hardware.door_open_duration_milliseconds,
+            ),
+            (
+                "hardware.door_stuck_timeout_milliseconds",
+                hardware.door_stuck_timeout_milliseconds,
+            ),
+            (
+                "hardware.homing_timeout_milliseconds",
+                hardware.homing_timeout_milliseconds,
+            ),
+            (
+                "hardware.order_expiry_seconds",
+                hardware.order_expiry_seconds,
+            ),
+            (
+                "network.heartbeat_interval_milliseconds",
+                network.heartbeat_interval_milliseconds,
+            ),
+            (
+                "network.peer_timeout_milliseconds",
+                network.peer_timeout_milliseconds,
+            ),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", field));
+            }
+        }
+        if hardware.door_stuck_timeout_milliseconds <= hardware.door_open_duration_milliseconds {
+            problems.push(format!(
+                "hardware.door_stuck_timeout_milliseconds ({}) must be longer than hardware.door_open_duration_milliseconds ({})",
+                hardware.door_stuck_timeout_milliseconds, hardware.door_open_duration_milliseconds
+            ));
+        }
+        // a single lost heartbeat must not make a peer look dead
+        if network.peer_timeout_milliseconds <= network.heartbeat_interval_milliseconds {
+            problems.push(format!(
+                "network.peer_timeout_milliseconds ({}) must be longer than network.heartbeat_interval_milliseconds ({})",
+                network.peer_timeout_milliseconds, network.heartbeat_interval_milliseconds
+            ));
+        }
+
+        check_address(
+            &mut problems,
+            "hardware.driver_address",
+            &hardware.driver_address,
+        );
+        check_address(&mut problems, "network.address", &network.address);
+
+        if storage.journal_path.is_empty() {
+            problems.push("storage.journal_path must not be empty".to_string());
+        }
+        if storage.journal_compaction_threshold == 0 {
+            problems
+                .push("storage.journal_compaction_threshold must be greater than 0".to_string());
+        }
+
+        if !scheduler::scheduler_names().any(|name| name == self.scheduling.scheduler) {
+            problems.push(format!(
+                "scheduling.scheduler '{}' is unknown, expected one of: {}",
+                self.scheduling.scheduler,
+                scheduler::scheduler_names().collect::<Vec<_>>().join(", ")
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+fn check_port(problems: &mut Vec<String>, field: &str, port: u32) {
+    if !(1..=u16::MAX as u32).contains(&port) {
+        problems.push(format!(
+            "{} must be between 1 and {}, got {}",
+            field,
+            u16::MAX,
+            port
+        ));
+    }
+}
+
+fn check_address(problems: &mut Vec<String>, field: &str, address: &str) {
+    match (address, 0)
+        .to_socket_addrs()
+        .map(|mut addresses| addresses.next())
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => problems.push(format!(
+            "{} '{}' does not resolve to any address",
+            field, address
+        )),
+        Err(error) => problems.push(format!(
+            "{} '{}' cannot be resolved: {}",
+            field, address, error
+        )),
+    }
+}
+
+// Load and validate the configuration file at the given path
+// Values are layered, each layer overriding the previous one: built-in defaults, the file,
+// the profile file next to it (config-<profile>.toml) and finally ELEVATOR_* variables.
+pub fn load(path: &str, profile: Option<&str>) -> Result<Config, ConfigError> {
+    let mut table = read_layer(path)?;
+
+    if let Some(profile) = profile {
+        let profile_path = Path::new(path).with_file_name(format!("config-{}.toml", profile));
+        merge(&mut table, read_layer(&profile_path.to_string_lossy())?);
+    }
+
+    merge(&mut table, environment_layer(std::env::vars())?);
+
+    let config = Config::deserialize(Value::Table(table))
+        .map_err(|error| ConfigError::Invalid(vec![error.message().to_string()]))?;
+    config.validate()?;
+    Ok(config)
+}
+
+// Read one configuration file, checking its values against the expected types
+fn read_layer(path: &str) -> Result<Table, ConfigError> {
+    let source = fs::read_to_string(path).map_err(|error| ConfigError::MissingFile {
+        path: path.to_string(),
+        error,
+    })?;
+
+    // parse as the real type first, so type errors point at the right line
+    toml::from_str::<Config>(&source).map_err(|error| ConfigError::parse(path, &source, &error))?;
+    toml::from_str(&source).map_err(|error| ConfigError::parse(path, &source, &error))
+}
+
+// Collect the values overridden by ELEVATOR_<SECTION>_<FIELD> variables
+fn environment_layer(
+    variables: impl Iterator<Item = (String, String)>,
+) -> Result<Table, ConfigError> {
+    let mut layer = Table::new();
+
+    for (variable, raw_value) in variables {
+        let Some(name) = variable.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let name = name.to_lowercase();
+        let Some((section, field)) = SECTIONS.iter().find_map(|section| {
+            name.strip_prefix(section)
+                .and_then(|rest| rest.strip_prefix('_'))
+                .map(|field| (*section, field))
+        }) else {
+            continue;
+        };
+
+        // numbers and booleans are written bare, anything that is not valid TOML is a string
+        let value = toml::from_str::<Table>(&format!("value = {}", raw_value))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| Value::String(raw_value.clone()));
+
+        let override_table = Table::from_iter([(
+            section.to_string(),
+            Value::Table(Table::from_iter([(field.to_string(), value)])),
+        )]);
+        Config::deserialize(Value::Table(override_table.clone())).map_err(|error| {
+            ConfigError::Environment {
+                variable: variable.clone(),
+                message: error.message().to_string(),
+            }
+        })?;
+        merge(&mut layer, override_table);
+    }
+    Ok(layer)
+}
+
+// Recursively merge the overrides into the base table
+fn merge(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(override_table)) => {
+                merge(base_table, override_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+// Errors that can occur when loading the configuration
+#[derive(Debug)]
+pub enum ConfigError {
+    MissingFile {
+        path: String,
+        error: io::Error,
+    },
+    Parse {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Environment {
+        variable: String,
+        message: String,
+    },
+    Invalid(Vec<String>), // every problem found by validation
+}
+
+impl ConfigError {
+    fn parse(path: &str, source: &str, error: &toml::de::Error) -> Self {
+        // spans are byte offsets, convert them to the 1-based position shown by editors
+        let offset = error.span().map_or(0, |span| span.start);
+        let before = &source[..offset.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rfind('\n')
+            .map_or(before.chars().count(), |newline| {
+                before[newline + 1..].chars().count()
+            })
+            + 1;
+
+        ConfigError::Parse {
+            path: path.to_string(),
+            line,
+            column,
+            message: error.message().to_string(),
+        }
+    }
 }
 
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingFile { path, error } => {
+                write!(f, "Failed to read config file {}: {}", path, error)
+            }
+            ConfigError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "Failed to parse {} at line {}, column {}: {}",
+                path, line, column, message
+            ),
+            ConfigError::Environment { variable, message } => {
+                write!(
+                    f,
+                    "Invalid value in environment variable {}: {}",
+                    variable, message
+                )
+            }
+            ConfigError::Invalid(problems) => {
+                write!(f, "Invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -68,10 +559,25 @@ mod tests {
                 driver_address: "localhost".to_string(),
                 driver_port: 15657,
                 driver_channel_poll_timeout_milliseconds: 25,
+                button_debounce_milliseconds: 20,
+                door_open_duration_milliseconds: 3000,
+                door_stuck_timeout_milliseconds: 20000,
+                homing_timeout_milliseconds: 10000,
+                halt_policy: HaltPolicy::StopAndKeepOrders,
+                order_expiry_seconds: 60,
             },
             network: NetworkConfig {
                 address: "192.168.1.100".to_string(),
                 port: 8080,
+                heartbeat_interval_milliseconds: 100,
+                peer_timeout_milliseconds: 1000,
+            },
+            storage: StorageConfig {
+                journal_path: "data/cab_orders.journal".to_string(),
+                journal_compaction_threshold: 1000,
+            },
+            scheduling: SchedulingConfig {
+                scheduler: "collective".to_string(),
             },
         };
 
@@ -81,6 +587,7 @@ mod tests {
         // Test individual components
         println!("Hardware only: {}", config.hardware);
         println!("Network only: {}", config.network);
+        println!("Storage only: {}", config.storage);
     }
 
     #[test]
@@ -91,10 +598,25 @@ mod tests {
                 driver_address: "127.0.0.1".to_string(),
                 driver_port: 9999,
                 driver_channel_poll_timeout_milliseconds: 50,
+                button_debounce_milliseconds: 20,
+                door_open_duration_milliseconds: 3000,
+                door_stuck_timeout_milliseconds: 20000,
+                homing_timeout_milliseconds: 10000,
+                halt_policy: HaltPolicy::StopAndKeepOrders,
+                order_expiry_seconds: 60,
             },
             network: NetworkConfig {
                 address: "0.0.0.0".to_string(),
                 port: 3000,
+                heartbeat_interval_milliseconds: 100,
+                peer_timeout_milliseconds: 1000,
+            },
+            storage: StorageConfig {
+                journal_path: "data/cab_orders.journal".to_string(),
+                journal_compaction_threshold: 1000,
+            },
+            scheduling: SchedulingConfig {
+                scheduler: "collective".to_string(),
             },
         };
 
@@ -102,9 +624,197 @@ mod tests {
         let debug_output = format!("{:?}", config);
         assert!(debug_output.contains("Config"));
         assert!(debug_output.contains("num_floors: 5"));
+        assert!(debug_output.contains("halt_policy: StopAndKeepOrders"));
 
         // Pretty debug output
         let pretty_debug = format!("{:#?}", config);
         println!("Pretty Debug:\n{}", pretty_debug);
     }
+
+    #[test]
+    fn test_halt_policy_parsing() {
+        let hardware: HardwareConfig = toml::from_str(
+            r#"
+            num_floors = 4
+            driver_address = "localhost"
+            driver_port = 15657
+            driver_channel_poll_timeout_milliseconds = 10
+            door_open_duration_milliseconds = 3000
+            door_stuck_timeout_milliseconds = 20000
+            homing_timeout_milliseconds = 10000
+            halt_policy = "nearest_floor_open_doors"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(hardware.halt_policy, HaltPolicy::NearestFloorOpenDoors);
+
+        let invalid = r#"
+            num_floors = 4
+            driver_address = "localhost"
+            driver_port = 15657
+            driver_channel_poll_timeout_milliseconds = 10
+            door_open_duration_milliseconds = 3000
+            door_stuck_timeout_milliseconds = 20000
+            homing_timeout_milliseconds = 10000
+            halt_policy = "ignore"
+            "#;
+        assert!(toml::from_str::<HardwareConfig>(invalid).is_err());
+    }
+
+    fn valid_config() -> Config {
+        load("config.toml", None).unwrap()
+    }
+
+    #[test]
+    fn test_load_valid_config() {
+        let config = valid_config();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_missing_file() {
+        assert!(matches!(
+            load("does-not-exist.toml", None),
+            Err(ConfigError::MissingFile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let source = "[hardware]\nnum_floors = 4\ndriver_port = \"high\"\n";
+        let error = toml::from_str::<Config>(source).unwrap_err();
+
+        match ConfigError::parse("broken.toml", source, &error) {
+            ConfigError::Parse { line, column, .. } => {
+                assert_eq!(line, 3);
+                assert_eq!(column, 15);
+            }
+            error => panic!("Unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let mut config = valid_config();
+        config.hardware.num_floors = 0;
+        config.hardware.driver_port = 70000;
+        config.network.port = 0;
+        config.hardware.door_open_duration_milliseconds = 0;
+        config.network.peer_timeout_milliseconds = config.network.heartbeat_interval_milliseconds;
+        config.network.address = "no such host.invalid".to_string();
+        config.scheduling.scheduler = "elevator".to_string();
+
+        match config.validate() {
+            Err(ConfigError::Invalid(problems)) => {
+                assert_eq!(problems.len(), 7, "{:#?}", problems);
+                assert!(problems[0].contains("num_floors"));
+                assert!(problems[1].contains("70000"));
+                assert!(problems
+                    .iter()
+                    .any(|problem| problem.contains("network.address")));
+                assert!(problems
+                    .iter()
+                    .any(|problem| problem.contains("'elevator' is unknown")));
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    fn variables(pairs: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_defaults_are_valid() {
+        let config = Config::default();
+        assert!(config.validate().is_ok());
+
+        // an empty file only uses the defaults
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.hardware.num_floors, 4);
+        assert_eq!(config.network.port, 1234);
+    }
+
+    #[test]
+    fn test_profiles_are_valid() {
+        for profile in ["dev", "prod"] {
+            let config = load("config.toml", Some(profile)).unwrap();
+            assert_eq!(config.hardware.num_floors, 4);
+        }
+        assert!(matches!(
+            load("config.toml", Some("staging")),
+            Err(ConfigError::MissingFile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let mut base: Table = toml::from_str(
+            r#"
+            [hardware]
+            num_floors = 6
+            driver_port = 15657
+            "#,
+        )
+        .unwrap();
+        let profile: Table = toml::from_str(
+            r#"
+            [hardware]
+            driver_port = 15658
+            "#,
+        )
+        .unwrap();
+        merge(&mut base, profile);
+        let environment = environment_layer(variables(&[
+            ("ELEVATOR_HARDWARE_DRIVER_ADDRESS", "elevator-simulator"),
+            ("ELEVATOR_NETWORK_PORT", "4321"),
+            ("ELEVATOR_HARDWARE_HALT_POLICY", "stop_and_clear_cab"),
+            ("ELEVATOR_SCHEDULING_SCHEDULER", "nearest"),
+            ("ELEVATOR_PROFILE", "dev"),
+            ("HOME", "/root"),
+        ]))
+        .unwrap();
+        merge(&mut base, environment);
+
+        let config = Config::deserialize(Value::Table(base)).unwrap();
+        assert_eq!(config.hardware.num_floors, 6);
+        assert_eq!(config.hardware.driver_port, 15658);
+        assert_eq!(config.hardware.driver_address, "elevator-simulator");
+        assert_eq!(config.hardware.halt_policy, HaltPolicy::StopAndClearCab);
+        assert_eq!(config.network.port, 4321);
+        assert_eq!(config.network.heartbeat_interval_milliseconds, 100);
+        assert_eq!(config.scheduling.scheduler, "nearest");
+    }
+
+    #[test]
+    fn test_invalid_environment_value() {
+        let result = environment_layer(variables(&[("ELEVATOR_HARDWARE_NUM_FLOORS", "many")]));
+        assert!(matches!(
+            result,
+            Err(ConfigError::Environment { variable, .. }) if variable == "ELEVATOR_HARDWARE_NUM_FLOORS"
+        ));
+    }
+
+    #[test]
+    fn test_structural_changes() {
+        let running = Config::default();
+        let mut reloaded = Config::default();
+        reloaded.hardware.door_open_duration_milliseconds = 5000;
+        reloaded.hardware.order_expiry_seconds = 120;
+        reloaded.scheduling.scheduler = "fifo".to_string();
+        assert!(running.structural_changes(&reloaded).is_empty());
+        assert_ne!(running.tunables(), reloaded.tunables());
+        assert_eq!(reloaded.tunables().scheduler, "fifo");
+
+        reloaded.hardware.num_floors = 6;
+        reloaded.network.port = 4321;
+        assert_eq!(
+            running.structural_changes(&reloaded),
+            vec!["hardware.num_floors", "network.port"]
+        );
+    }
 }
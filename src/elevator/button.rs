@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+
+use super::events::ButtonKind;
+
+const NUM_CALL_VARIANTS: usize = ButtonKind::ALL.len();
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ButtonState {
+    is_pressed: bool,                // debounced state
+    changing_since: Option<Instant>, // when the raw reading started to differ from it
+}
+
+// Debounces the raw button readings and turns them into edges
+// A reading has to differ from the debounced state for the whole debounce window before the
+// state flips, so contact bounce and single noisy samples never reach the upper layers.
+#[derive(Debug)]
+pub struct ButtonDebouncer {
+    debounce: Duration,
+    buttons: Vec<[ButtonState; NUM_CALL_VARIANTS]>,
+}
+
+impl ButtonDebouncer {
+    pub fn new(num_floors: u8, debounce: Duration) -> Self {
+        Self {
+            debounce,
+            buttons: vec![[ButtonState::default(); NUM_CALL_VARIANTS]; num_floors as usize],
+        }
+    }
+
+    pub fn set_debounce(&mut self, debounce: Duration) {
+        self.debounce = debounce;
+    }
+
+    // Feed a raw reading, returns the new debounced state if it flipped
+    pub fn update(
+        &mut self,
+        floor: u8,
+        kind: ButtonKind,
+        is_pressed: bool,
+        now: Instant,
+    ) -> Option<bool> {
+        let button = self
+            .buttons
+            .get_mut(floor as usize)?
+            .get_mut(kind.call_type() as usize)?;
+
+        if is_pressed == button.is_pressed {
+            button.changing_since = None;
+            return None;
+        }
+
+        let changing_since = *button.changing_since.get_or_insert(now);
+        if now.duration_since(changing_since) < self.debounce {
+            return None;
+        }
+        button.is_pressed = is_pressed;
+        button.changing_since = None;
+        Some(is_pressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_press_and_release_after_debounce() {
+        let start = Instant::now();
+        let mut debouncer = ButtonDebouncer::new(4, Duration::from_millis(20));
+        let at = |milliseconds| start + Duration::from_millis(milliseconds);
+
+        assert_eq!(debouncer.update(2, ButtonKind::Cab, true, at(0)), None);
+        assert_eq!(debouncer.update(2, ButtonKind::Cab, true, at(10)), None);
+        assert_eq!(
+            debouncer.update(2, ButtonKind::Cab, true, at(20)),
+            Some(true)
+        );
+        // held down, nothing more to report
+        assert_eq!(debouncer.update(2, ButtonKind::Cab, true, at(500)), None);
+
+        assert_eq!(debouncer.update(2, ButtonKind::Cab, false, at(510)), None);
+        assert_eq!(
+            debouncer.update(2, ButtonKind::Cab, false, at(530)),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_bounces_are_filtered() {
+        let start = Instant::now();
+        let mut debouncer = ButtonDebouncer::new(4, Duration::from_millis(20));
+        let at = |milliseconds| start + Duration::from_millis(milliseconds);
+
+        // a glitch shorter than the window restarts it
+        assert_eq!(debouncer.update(1, ButtonKind::HallUp, true, at(0)), None);
+        assert_eq!(debouncer.update(1, ButtonKind::HallUp, false, at(5)), None);
+        assert_eq!(debouncer.update(1, ButtonKind::HallUp, true, at(10)), None);
+        assert_eq!(debouncer.update(1, ButtonKind::HallUp, true, at(25)), None);
+        assert_eq!(
+            debouncer.update(1, ButtonKind::HallUp, true, at(30)),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_zero_debounce_reports_immediately() {
+        let now = Instant::now();
+        let mut debouncer = ButtonDebouncer::new(4, Duration::ZERO);
+        assert_eq!(debouncer.update(0, ButtonKind::Cab, true, now), Some(true));
+        assert_eq!(
+            debouncer.update(0, ButtonKind::Cab, false, now),
+            Some(false)
+        );
+
+        // unknown floors are ignored
+        assert_eq!(debouncer.update(7, ButtonKind::Cab, true, now), None);
+    }
+}
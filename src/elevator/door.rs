@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorEvent {
+    // The door has been open and unobstructed for the full open duration
+    TimedOut,
+    // The door has been held open by an obstruction for longer than the stuck timeout
+    Stuck,
+    // A stuck door is no longer obstructed
+    Unstuck,
+}
+
+// Door controller with a resettable countdown
+// The countdown only runs while the doorway is clear, so the door never closes while obstructed.
+#[derive(Debug)]
+pub struct DoorController {
+    open_duration: Duration,
+    stuck_timeout: Duration,
+    is_open: bool,
+    is_obstructed: bool,
+    is_stuck: bool,
+    close_deadline: Option<Instant>,
+    held_since: Option<Instant>, // when the obstruction started holding the open door
+}
+
+impl DoorController {
+    pub fn new(open_duration: Duration, stuck_timeout: Duration) -> Self {
+        Self {
+            open_duration,
+            stuck_timeout,
+            is_open: false,
+            is_obstructed: false,
+            is_stuck: false,
+            close_deadline: None,
+            held_since: None,
+        }
+    }
+
+    // Change the timing, takes effect the next time a countdown starts
+    pub fn set_durations(&mut self, open_duration: Duration, stuck_timeout: Duration) {
+        self.open_duration = open_duration;
+        self.stuck_timeout = stuck_timeout;
+    }
+
+    // Open the door, or restart the countdown if it is already open
+    pub fn open(&mut self, now: Instant) {
+        self.is_open = true;
+        self.close_deadline = Some(now + self.open_duration);
+        if self.is_obstructed && self.held_since.is_none() {
+            self.held_since = Some(now);
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.is_open = false;
+        self.close_deadline = None;
+        self.held_since = None;
+    }
+
+    // Update the obstruction state, returns Unstuck if this clears a stuck door
+    pub fn set_obstructed(&mut self, is_obstructed: bool, now: Instant) -> Option<DoorEvent> {
+        if is_obstructed == self.is_obstructed {
+            return None;
+        }
+        self.is_obstructed = is_obstructed;
+
+        if is_obstructed {
+            if self.is_open {
+                self.held_since = Some(now);
+            }
+            return None;
+        }
+
+        // the countdown starts over once the doorway is clear
+        self.held_since = None;
+        if self.is_open {
+            self.close_deadline = Some(now + self.open_duration);
+        }
+        if self.is_stuck {
+            self.is_stuck = false;
+            return Some(DoorEvent::Unstuck);
+        }
+        None
+    }
+
+    // Check the timers, returns the event that is due at the given time, if any
+    pub fn poll(&mut self, now: Instant) -> Option<DoorEvent> {
+        if !self.is_open {
+            return None;
+        }
+
+        if self.is_obstructed {
+            let held_since = self.held_since?;
+            if !self.is_stuck && now >= held_since + self.stuck_timeout {
+                self.is_stuck = true;
+                return Some(DoorEvent::Stuck);
+            }
+            return None;
+        }
+
+        match self.close_deadline {
+            Some(deadline) if now >= deadline => {
+                self.close_deadline = None;
+                Some(DoorEvent::TimedOut)
+            }
+            _ => None,
+        }
+    }
+
+    // The next point in time where poll may produce an event
+    pub fn next_deadline(&self) -> Option<Instant> {
+        if !self.is_open {
+            return None;
+        }
+
+        if self.is_obstructed {
+            if self.is_stuck {
+                return None;
+            }
+            return self.held_since.map(|since| since + self.stuck_timeout);
+        }
+
+        self.close_deadline
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    pub fn is_obstructed(&self) -> bool {
+        self.is_obstructed
+    }
+
+    pub fn is_stuck(&self) -> bool {
+        self.is_stuck
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPEN: Duration = Duration::from_secs(3);
+    const STUCK: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn test_door_times_out_after_open_duration() {
+        let start = Instant::now();
+        let mut door = DoorController::new(OPEN, STUCK);
+
+        door.open(start);
+        assert!(door.is_open());
+        assert_eq!(door.next_deadline(), Some(start + OPEN));
+        assert_eq!(door.poll(start + Duration::from_secs(2)), None);
+        assert_eq!(door.poll(start + OPEN), Some(DoorEvent::TimedOut));
+        assert_eq!(door.poll(start + OPEN), None);
+    }
+
+    #[test]
+    fn test_door_never_times_out_while_obstructed() {
+        let start = Instant::now();
+        let mut door = DoorController::new(OPEN, STUCK);
+
+        door.open(start);
+        door.set_obstructed(true, start + Duration::from_secs(1));
+        assert_eq!(door.poll(start + Duration::from_secs(5)), None);
+
+        // countdown restarts from the moment the obstruction clears
+        let cleared = start + Duration::from_secs(6);
+        door.set_obstructed(false, cleared);
+        assert_eq!(door.poll(cleared + Duration::from_secs(2)), None);
+        assert_eq!(door.poll(cleared + OPEN), Some(DoorEvent::TimedOut));
+    }
+
+    #[test]
+    fn test_door_reports_stuck_and_unstuck() {
+        let start = Instant::now();
+        let mut door = DoorController::new(OPEN, STUCK);
+
+        door.set_obstructed(true, start);
+        door.open(start + Duration::from_secs(1));
+        assert_eq!(
+            door.next_deadline(),
+            Some(start + Duration::from_secs(1) + STUCK)
+        );
+
+        let stuck_at = start + Duration::from_secs(1) + STUCK;
+        assert_eq!(door.poll(stuck_at), Some(DoorEvent::Stuck));
+        assert!(door.is_stuck());
+        assert_eq!(door.poll(stuck_at + STUCK), None);
+        assert_eq!(door.next_deadline(), None);
+
+        let cleared = stuck_at + Duration::from_secs(1);
+        assert_eq!(
+            door.set_obstructed(false, cleared),
+            Some(DoorEvent::Unstuck)
+        );
+        assert!(!door.is_stuck());
+        assert_eq!(door.poll(cleared + OPEN), Some(DoorEvent::TimedOut));
+    }
+
+    #[test]
+    fn test_obstruction_while_closed_is_ignored() {
+        let start = Instant::now();
+        let mut door = DoorController::new(OPEN, STUCK);
+
+        door.set_obstructed(true, start);
+        assert_eq!(door.next_deadline(), None);
+        assert_eq!(door.poll(start + STUCK), None);
+        assert!(!door.is_stuck());
+    }
+}
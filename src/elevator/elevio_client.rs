@@ -0,0 +1,186 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use super::io::{ElevatorIo, MESSAGE_BYTES};
+
+// How long the elevator server may take to accept or answer before the connection counts as lost
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Client side of the elevio protocol that survives losing the elevator server
+// driver_rust panics on any socket error, this client returns the error instead and drops the
+// broken connection, so every later request fails fast until reconnect succeeds. Clones share
+// the connection.
+#[derive(Debug, Clone)]
+pub struct ElevioClient {
+    address: String,
+    num_floors: u8,
+    stream: Arc<Mutex<Option<TcpStream>>>, // None while disconnected
+}
+
+impl ElevioClient {
+    pub fn connect(address: &str, num_floors: u8) -> io::Result<ElevioClient> {
+        Ok(ElevioClient {
+            address: address.to_string(),
+            num_floors,
+            stream: Arc::new(Mutex::new(Some(open(address)?))),
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.lock().is_some()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<TcpStream>> {
+        self.stream
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    // Send a request that has no reply
+    fn write(&self, request: [u8; MESSAGE_BYTES]) -> io::Result<()> {
+        self.exchange(request, false).map(|_| ())
+    }
+
+    // Send a request and wait for its reply
+    fn read(&self, request: [u8; MESSAGE_BYTES]) -> io::Result<[u8; MESSAGE_BYTES]> {
+        self.exchange(request, true)
+    }
+
+    fn exchange(
+        &self,
+        request: [u8; MESSAGE_BYTES],
+        has_reply: bool,
+    ) -> io::Result<[u8; MESSAGE_BYTES]> {
+        let mut stream = self.lock();
+        let Some(connection) = stream.as_mut() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                format!("not connected to {}", self.address),
+            ));
+        };
+
+        let mut reply = [0; MESSAGE_BYTES];
+        let result = connection
+            .write_all(&request)
+            .and_then(|()| match has_reply {
+                true => connection.read_exact(&mut reply),
+                false => Ok(()),
+            });
+        if result.is_err() {
+            // a half read reply would leave the stream out of step, start over on reconnect
+            *stream = None;
+        }
+        result.map(|()| reply)
+    }
+}
+
+// Connect within the response timeout, a plain connect to an unreachable host can hang for minutes
+fn open(address: &str) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{} does not resolve to any address", address),
+    );
+    for socket_address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_address, RESPONSE_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_nodelay(true)?;
+                stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+                stream.set_write_timeout(Some(RESPONSE_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(error) => last_error = error,
+        }
+    }
+    Err(last_error)
+}
+
+impl ElevatorIo for ElevioClient {
+    fn num_floors(&self) -> u8 {
+        self.num_floors
+    }
+
+    fn motor_direction(&self, direction: u8) -> io::Result<()> {
+        self.write([1, direction, 0, 0])
+    }
+
+    fn call_button(&self, floor: u8, call_type: u8) -> io::Result<bool> {
+        Ok(self.read([6, call_type, floor, 0])?[1] != 0)
+    }
+
+    fn call_button_light(&self, floor: u8, call_type: u8, is_lit: bool) -> io::Result<()> {
+        self.write([2, call_type, floor, is_lit as u8])
+    }
+
+    fn floor_sensor(&self) -> io::Result<Option<u8>> {
+        let reply = self.read([7, 0, 0, 0])?;
+        Ok((reply[1] != 0).then_some(reply[2]))
+    }
+
+    fn stop_button(&self) -> io::Result<bool> {
+        Ok(self.read([8, 0, 0, 0])?[1] != 0)
+    }
+
+    fn stop_button_light(&self, is_lit: bool) -> io::Result<()> {
+        self.write([5, is_lit as u8, 0, 0])
+    }
+
+    fn obstruction(&self) -> io::Result<bool> {
+        Ok(self.read([9, 0, 0, 0])?[1] != 0)
+    }
+
+    fn door_light(&self, is_lit: bool) -> io::Result<()> {
+        self.write([4, is_lit as u8, 0, 0])
+    }
+
+    fn floor_indicator(&self, floor: u8) -> io::Result<()> {
+        self.write([3, floor, 0, 0])
+    }
+
+    fn reconnect(&self) -> io::Result<()> {
+        let stream = open(&self.address)?;
+        *self.lock() = Some(stream);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_lost_connection_is_reported_and_restored() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let client = ElevioClient::connect(&address, 4).unwrap();
+
+        let (mut server, _) = listener.accept().unwrap();
+        client.door_light(true).unwrap();
+        let mut request = [0; MESSAGE_BYTES];
+        server.read_exact(&mut request).unwrap();
+        assert_eq!(request, [4, 1, 0, 0]);
+
+        // the server goes away, the next read fails and so does everything after it
+        drop(server);
+        assert!(client.floor_sensor().is_err());
+        assert!(!client.is_connected());
+        assert_eq!(
+            client.door_light(false).unwrap_err().kind(),
+            io::ErrorKind::NotConnected
+        );
+
+        client.reconnect().unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        let replier = thread::spawn(move || {
+            let mut request = [0; MESSAGE_BYTES];
+            server.read_exact(&mut request).unwrap();
+            assert_eq!(request, [7, 0, 0, 0]);
+            server.write_all(&[7, 1, 2, 0]).unwrap();
+        });
+        assert_eq!(client.floor_sensor().unwrap(), Some(2));
+        replier.join().unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel as channel;
+use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+use log::{info, warn};
+
+use super::io::{ElevatorIo, MESSAGE_BYTES};
+use super::simulator::{SimulatedElevator, SimulatorEvent};
+
+// How often the listener checks for new clients and termination
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Virtual time between the press and release of a tapped button
+const TAP_DURATION: Duration = Duration::from_millis(250);
+
+// Serves the elevio TCP protocol on top of a simulated elevator
+// A drop-in replacement for the elevator server: the driver connects, writes 4 byte requests
+// and reads 4 byte replies for the requests that ask for an input. Every client gets its own
+// thread and all of them share the same elevator, so a reconnecting driver finds the car where
+// it left it.
+pub struct ElevioServer {
+    listener: TcpListener,
+    elevator: SimulatedElevator,
+    terminate_rx: channel::Receiver<()>,
+}
+
+impl ElevioServer {
+    pub fn bind(
+        address: &str,
+        elevator: SimulatedElevator,
+        terminate_rx: channel::Receiver<()>,
+    ) -> io::Result<ElevioServer> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(ElevioServer {
+            listener,
+            elevator,
+            terminate_rx,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn run(self) {
+        if let Ok(address) = self.local_addr() {
+            info!("Serving simulated elevator on {}", address);
+        }
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    info!("Client {} connected", peer);
+                    let elevator = self.elevator.clone();
+                    thread::spawn(move || match serve_client(stream, &elevator) {
+                        Ok(()) => info!("Client {} disconnected", peer),
+                        Err(error) => warn!("Client {} dropped: {}", peer, error),
+                    });
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+                Err(error) => warn!("Failed to accept client: {}", error),
+            }
+
+            channel::select! {
+              recv(self.terminate_rx) -> _ => {
+                break;
+              }
+              default(ACCEPT_POLL_INTERVAL) => {}
+            }
+        }
+    }
+}
+
+// Answer requests until the client closes the connection
+fn serve_client(mut stream: TcpStream, elevator: &SimulatedElevator) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+
+    let mut request = [0; MESSAGE_BYTES];
+    loop {
+        match stream.read_exact(&mut request) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(error) => return Err(error),
+        }
+        if let Some(reply) = handle_request(elevator, request)? {
+            stream.write_all(&reply)?;
+        }
+    }
+}
+
+// Apply one elevio request, returns the reply if the request expects one
+pub fn handle_request(
+    elevator: &impl ElevatorIo,
+    request: [u8; MESSAGE_BYTES],
+) -> io::Result<Option<[u8; MESSAGE_BYTES]>> {
+    let is_valid_button =
+        |call_type: u8, floor: u8| call_type <= CAB && floor < elevator.num_floors();
+
+    match request {
+        [1, direction, _, _] => elevator.motor_direction(direction)?,
+        [2, call_type, floor, is_lit] if is_valid_button(call_type, floor) => {
+            elevator.call_button_light(floor, call_type, is_lit != 0)?
+        }
+        [3, floor, _, _] if floor < elevator.num_floors() => elevator.floor_indicator(floor)?,
+        [4, is_lit, _, _] => elevator.door_light(is_lit != 0)?,
+        [5, is_lit, _, _] => elevator.stop_button_light(is_lit != 0)?,
+        [6, call_type, floor, _] => {
+            let is_pressed =
+                is_valid_button(call_type, floor) && elevator.call_button(floor, call_type)?;
+            return Ok(Some([6, is_pressed as u8, 0, 0]));
+        }
+        [7, _, _, _] => {
+            return Ok(Some(match elevator.floor_sensor()? {
+                Some(floor) => [7, 1, floor, 0],
+                None => [7, 0, 0, 0],
+            }));
+        }
+        [8, _, _, _] => return Ok(Some([8, elevator.stop_button()? as u8, 0, 0])),
+        [9, _, _, _] => return Ok(Some([9, elevator.obstruction()? as u8, 0, 0])),
+        _ => warn!("Ignoring invalid request {:?}", request),
+    }
+    Ok(None)
+}
+
+// A line of the control interface, typed on the console or read from a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    Events(Vec<(Duration, SimulatorEvent)>), // with the delay before each event
+    Status,
+}
+
+// Parse a control line, returns None for blank lines and comments
+//   press|release|tap <floor> <up|down|cab>
+//   obstruction|stop <on|off>
+//   status
+//   after <milliseconds> <command>
+pub fn parse_control_line(line: &str) -> Result<Option<ControlCommand>, String> {
+    let line = line.split('#').next().unwrap_or_default();
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.is_empty() {
+        return Ok(None);
+    }
+    parse_control_words(&words, Duration::ZERO).map(Some)
+}
+
+fn parse_control_words(words: &[&str], delay: Duration) -> Result<ControlCommand, String> {
+    let events = match words {
+        ["after", milliseconds, command @ ..] if !command.is_empty() => {
+            let milliseconds: u64 = milliseconds
+                .parse()
+                .map_err(|_| format!("invalid delay '{}'", milliseconds))?;
+            return parse_control_words(command, delay + Duration::from_millis(milliseconds));
+        }
+        ["status"] if delay.is_zero() => return Ok(ControlCommand::Status),
+        ["press", floor, call_type] => {
+            let (floor, call_type) = parse_button(floor, call_type)?;
+            vec![(delay, SimulatorEvent::Press(floor, call_type))]
+        }
+        ["release", floor, call_type] => {
+            let (floor, call_type) = parse_button(floor, call_type)?;
+            vec![(delay, SimulatorEvent::Release(floor, call_type))]
+        }
+        ["tap", floor, call_type] => {
+            let (floor, call_type) = parse_button(floor, call_type)?;
+            vec![
+                (delay, SimulatorEvent::Press(floor, call_type)),
+                (
+                    delay + TAP_DURATION,
+                    SimulatorEvent::Release(floor, call_type),
+                ),
+            ]
+        }
+        ["obstruction", state] => {
+            vec![(delay, SimulatorEvent::SetObstruction(parse_switch(state)?))]
+        }
+        ["stop", state] => vec![(delay, SimulatorEvent::SetStopButton(parse_switch(state)?))],
+        _ => return Err(format!("unknown command '{}'", words.join(" "))),
+    };
+    Ok(ControlCommand::Events(events))
+}
+
+fn parse_button(floor: &str, call_type: &str) -> Result<(u8, u8), String> {
+    let floor = floor
+        .parse()
+        .map_err(|_| format!("invalid floor '{}'", floor))?;
+    let call_type = match call_type {
+        "up" => HALL_UP,
+        "down" => HALL_DOWN,
+        "cab" => CAB,
+        _ => {
+            return Err(format!(
+                "invalid button '{}', expected up, down or cab",
+                call_type
+            ))
+        }
+    };
+    Ok((floor, call_type))
+}
+
+fn parse_switch(state: &str) -> Result<bool, String> {
+    match state {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("invalid state '{}', expected on or off", state)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::simulator::SimulatorConfig;
+    use driver_rust::elevio::elev::{Elevator, DIRN_UP};
+
+    fn setup_server() -> (SimulatedElevator, SocketAddr, channel::Sender<()>) {
+        let elevator = SimulatedElevator::new(SimulatorConfig {
+            time_scale: 0.0,
+            ..SimulatorConfig::default()
+        });
+        let (terminate_tx, terminate_rx) = channel::unbounded();
+        let server = ElevioServer::bind("127.0.0.1:0", elevator.clone(), terminate_rx).unwrap();
+        let address = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        (elevator, address, terminate_tx)
+    }
+
+    #[test]
+    fn test_driver_client_talks_to_server() {
+        let (elevator, address, _terminate_tx) = setup_server();
+        let client = Elevator::init(&address.to_string(), 4).unwrap();
+
+        assert_eq!(client.floor_sensor(), Some(0));
+        elevator.press(2, CAB);
+        assert!(client.call_button(2, CAB));
+        assert!(!client.call_button(2, HALL_UP));
+
+        client.call_button_light(2, CAB, true);
+        client.door_light(true);
+        client.motor_direction(DIRN_UP);
+        elevator.set_obstruction(true);
+        assert!(client.obstruction());
+        assert!(!client.stop_button());
+
+        // the writes have been applied once a later read is answered
+        assert!(elevator.is_button_lit(2, CAB));
+        assert!(elevator.is_door_lit());
+        elevator.advance(Duration::from_secs(1));
+        assert_eq!(client.floor_sensor(), None);
+        elevator.advance(Duration::from_secs(1));
+        assert_eq!(client.floor_sensor(), Some(1));
+    }
+
+    #[test]
+    fn test_reconnecting_client_keeps_state() {
+        let (elevator, address, _terminate_tx) = setup_server();
+        {
+            let client = Elevator::init(&address.to_string(), 4).unwrap();
+            client.floor_indicator(3);
+            assert_eq!(client.floor_sensor(), Some(0));
+        }
+
+        let client = Elevator::init(&address.to_string(), 4).unwrap();
+        assert_eq!(client.floor_sensor(), Some(0));
+        assert_eq!(elevator.current_floor_indicator(), Some(3));
+    }
+
+    #[test]
+    fn test_invalid_requests_are_ignored() {
+        let elevator = SimulatedElevator::new(SimulatorConfig::default());
+        assert_eq!(handle_request(&elevator, [2, CAB, 9, 1]).unwrap(), None);
+        assert!(!elevator.is_button_lit(9, CAB));
+        assert_eq!(
+            handle_request(&elevator, [6, 7, 0, 0]).unwrap(),
+            Some([6, 0, 0, 0])
+        );
+        assert_eq!(handle_request(&elevator, [42, 0, 0, 0]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_control_lines() {
+        assert_eq!(parse_control_line("  # comment"), Ok(None));
+        assert_eq!(
+            parse_control_line("status"),
+            Ok(Some(ControlCommand::Status))
+        );
+        assert_eq!(
+            parse_control_line("press 2 cab"),
+            Ok(Some(ControlCommand::Events(vec![(
+                Duration::ZERO,
+                SimulatorEvent::Press(2, CAB)
+            )])))
+        );
+        assert_eq!(
+            parse_control_line("after 1500 tap 1 down"),
+            Ok(Some(ControlCommand::Events(vec![
+                (
+                    Duration::from_millis(1500),
+                    SimulatorEvent::Press(1, HALL_DOWN)
+                ),
+                (
+                    Duration::from_millis(1500) + TAP_DURATION,
+                    SimulatorEvent::Release(1, HALL_DOWN)
+                ),
+            ])))
+        );
+        assert_eq!(
+            parse_control_line("after 10 after 20 obstruction on"),
+            Ok(Some(ControlCommand::Events(vec![(
+                Duration::from_millis(30),
+                SimulatorEvent::SetObstruction(true)
+            )])))
+        );
+
+        assert!(parse_control_line("press two cab").is_err());
+        assert!(parse_control_line("press 1 sideways").is_err());
+        assert!(parse_control_line("stop maybe").is_err());
+        assert!(parse_control_line("after 100 status").is_err());
+        assert!(parse_control_line("jump").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+
+use crate::queue::{Direction, Order};
+
+// The buttons found on every floor, in the order of the elevator server call types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ButtonKind {
+    HallUp,
+    HallDown,
+    Cab,
+}
+
+impl ButtonKind {
+    pub const ALL: [ButtonKind; 3] = [ButtonKind::HallUp, ButtonKind::HallDown, ButtonKind::Cab];
+
+    // Direction of the hall call placed by the button, None for the cab button
+    pub fn direction(self) -> Option<Direction> {
+        match self {
+            ButtonKind::HallUp => Some(Direction::Up),
+            ButtonKind::HallDown => Some(Direction::Down),
+            ButtonKind::Cab => None,
+        }
+    }
+
+    // Call type of the button in the elevator server protocol
+    pub fn call_type(self) -> u8 {
+        match self {
+            ButtonKind::HallUp => HALL_UP,
+            ButtonKind::HallDown => HALL_DOWN,
+            ButtonKind::Cab => CAB,
+        }
+    }
+
+    pub fn from_call_type(call_type: u8) -> Option<ButtonKind> {
+        match call_type {
+            HALL_UP => Some(ButtonKind::HallUp),
+            HALL_DOWN => Some(ButtonKind::HallDown),
+            CAB => Some(ButtonKind::Cab),
+            _ => None,
+        }
+    }
+}
+
+// Hall button that places a call in the given direction
+impl From<Direction> for ButtonKind {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Up => ButtonKind::HallUp,
+            Direction::Down => ButtonKind::HallDown,
+        }
+    }
+}
+
+// Button whose light shows the order, cab commands are lit on the cab button
+impl From<&Order> for ButtonKind {
+    fn from(order: &Order) -> Self {
+        match order {
+            Order::Call(call) => call.direction.into(),
+            Order::Command(_) => ButtonKind::Cab,
+        }
+    }
+}
+
+// Input changes reported by the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HardwareEvent {
+    ButtonPressed { floor: u8, kind: ButtonKind },
+    ButtonReleased { floor: u8, kind: ButtonKind },
+    // pressed while its light was already on, e.g. to ask for the order to be confirmed again
+    ButtonRepressed { floor: u8, kind: ButtonKind },
+    FloorReached(u8),
+    Obstruction(bool),
+    Stop(bool),
+    // the elevator server cannot be reached, inputs are stale and outputs wait for it to return
+    HardwareUnavailable,
+    // reconnected, every output has been applied again
+    HardwareRestored,
+}
+
+// Outputs applied by the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HardwareCommand {
+    SetMotor(Option<Direction>), // None stops the motor
+    SetButtonLight {
+        floor: u8,
+        kind: ButtonKind,
+        is_lit: bool,
+    },
+    SetFloorIndicator(u8),
+    SetDoorLight(bool),
+    SetStopLight(bool),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::init_clock_with_random_id;
+    use crate::queue::{Call, Command};
+
+    #[test]
+    fn test_button_kind_mapping() {
+        let _ = init_clock_with_random_id();
+
+        for kind in ButtonKind::ALL {
+            assert_eq!(ButtonKind::from_call_type(kind.call_type()), Some(kind));
+        }
+        assert_eq!(ButtonKind::from_call_type(3), None);
+
+        assert_eq!(
+            ButtonKind::from(Direction::Up).direction(),
+            Some(Direction::Up)
+        );
+        assert_eq!(
+            ButtonKind::from(Direction::Down).direction(),
+            Some(Direction::Down)
+        );
+        assert_eq!(ButtonKind::Cab.direction(), None);
+
+        let call = Order::from(Call::new(1, Direction::Down));
+        let command = Order::from(Command::new(1));
+        assert_eq!(ButtonKind::from(&call), ButtonKind::HallDown);
+        assert_eq!(ButtonKind::from(&command), ButtonKind::Cab);
+    }
+}
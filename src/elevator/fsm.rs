@@ -0,0 +1,1247 @@
+use std::time::{Duration, Instant};
+
+use crossbeam_channel as channel;
+
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::door::{DoorController, DoorEvent};
+use super::events::{ButtonKind, HardwareCommand, HardwareEvent};
+use crate::config::{HaltPolicy, HardwareConfig, Tunables};
+use crate::queue::assigner::ElevatorStatus;
+use crate::queue::scheduler::{self, PeerState, Scheduler, SchedulerContext};
+use crate::queue::{Call, Command, Direction, Order, OrderQueue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ElevatorState {
+    Idle,
+    Moving,
+    DoorOpen,
+    Halted,
+}
+
+pub struct ElevatorFsm {
+    state: ElevatorState,
+    num_floors: u8,
+    elevator_id: Uuid,
+    current_floor: Option<u8>, // unknown until the driver has homed to a floor
+    direction: Option<Direction>,
+    is_between_floors: bool,
+    last_floor_at: Option<Instant>,
+    is_motor_running: bool,
+    is_available: bool,
+    is_hardware_reachable: bool,
+    published_status: Option<ElevatorStatus>,
+    peer_states: Vec<PeerState>,
+    halt_policy: HaltPolicy,
+    order_expiry_seconds: u64,
+    door: DoorController,
+    queue: OrderQueue,
+    scheduler: Box<dyn Scheduler + Send>, // replaced when the configuration picks another
+    hw_command_tx: channel::Sender<HardwareCommand>,
+    hw_event_rx: channel::Receiver<HardwareEvent>,
+    hall_request_tx: channel::Sender<(u8, Direction)>,
+    hall_call_rx: channel::Receiver<Call>,
+    hall_call_released_rx: channel::Receiver<Call>,
+    hall_call_served_tx: channel::Sender<Call>,
+    cab_orders_tx: channel::Sender<Vec<Command>>,
+    restored_commands_rx: channel::Receiver<Vec<Command>>,
+    tunables_rx: channel::Receiver<Tunables>,
+    availability_tx: channel::Sender<bool>,
+    status_tx: channel::Sender<ElevatorStatus>,
+    peer_states_rx: channel::Receiver<Vec<PeerState>>,
+    terminate_rx: channel::Receiver<()>,
+}
+
+impl ElevatorFsm {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        config: &HardwareConfig,
+        elevator_id: Uuid,
+        scheduler: Box<dyn Scheduler + Send>,
+        queue: OrderQueue,
+        hw_command_tx: channel::Sender<HardwareCommand>,
+        hw_event_rx: channel::Receiver<HardwareEvent>,
+        hall_request_tx: channel::Sender<(u8, Direction)>,
+        hall_call_rx: channel::Receiver<Call>,
+        hall_call_released_rx: channel::Receiver<Call>,
+        hall_call_served_tx: channel::Sender<Call>,
+        cab_orders_tx: channel::Sender<Vec<Command>>,
+        restored_commands_rx: channel::Receiver<Vec<Command>>,
+        tunables_rx: channel::Receiver<Tunables>,
+        availability_tx: channel::Sender<bool>,
+        status_tx: channel::Sender<ElevatorStatus>,
+        peer_states_rx: channel::Receiver<Vec<PeerState>>,
+        terminate_rx: channel::Receiver<()>,
+    ) -> ElevatorFsm {
+        ElevatorFsm {
+            state: ElevatorState::Idle,
+            num_floors: config.num_floors,
+            elevator_id,
+            current_floor: None,
+            direction: None,
+            is_between_floors: false,
+            last_floor_at: None,
+            is_motor_running: false,
+            is_available: true,
+            is_hardware_reachable: true,
+            published_status: None,
+            peer_states: Vec::new(),
+            halt_policy: config.halt_policy,
+            order_expiry_seconds: config.order_expiry_seconds,
+            door: DoorController::new(
+                Duration::from_millis(config.door_open_duration_milliseconds),
+                Duration::from_millis(config.door_stuck_timeout_milliseconds),
+            ),
+            queue,
+            scheduler,
+            hw_command_tx,
+            hw_event_rx,
+            hall_request_tx,
+            hall_call_rx,
+            hall_call_released_rx,
+            hall_call_served_tx,
+            cab_orders_tx,
+            restored_commands_rx,
+            tunables_rx,
+            availability_tx,
+            status_tx,
+            peer_states_rx,
+            terminate_rx,
+        }
+    }
+
+    pub fn state(&self) -> ElevatorState {
+        self.state
+    }
+
+    // Whether this elevator can take hall calls, false while halted, the door is stuck or the
+    // hardware is unreachable
+    pub fn is_available(&self) -> bool {
+        self.is_available
+    }
+
+    pub fn run(mut self) {
+        info!(
+            "Starting elevator state machine with {} scheduler",
+            self.scheduler.name()
+        );
+        self.restore_cab_lights();
+        // only share the commands if there are any, an empty set could erase the peers' backup
+        if self.queue.count_commands() > 0 {
+            self.publish_cab_orders();
+        }
+
+        loop {
+            let door_timer = match self.door.next_deadline() {
+                Some(deadline) => channel::at(deadline),
+                None => channel::never(),
+            };
+
+            channel::select! {
+              recv(self.hw_event_rx) -> msg => {
+                match msg {
+                  Ok(event) => self.on_hardware_event(event),
+                  Err(error) => {
+                    error!("Lost connection to hardware event channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.hall_call_rx) -> msg => {
+                match msg {
+                  Ok(call) => self.on_hall_call(call),
+                  Err(error) => {
+                    error!("Lost connection to hall call channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.hall_call_released_rx) -> msg => {
+                match msg {
+                  Ok(call) => self.on_hall_call_released(call),
+                  Err(error) => {
+                    error!("Lost connection to released hall call channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.restored_commands_rx) -> msg => {
+                match msg {
+                  Ok(commands) => self.on_restored_commands(commands),
+                  Err(error) => {
+                    error!("Lost connection to restored command channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.peer_states_rx) -> msg => {
+                match msg {
+                  Ok(peer_states) => self.peer_states = peer_states,
+                  Err(error) => {
+                    error!("Lost connection to peer state channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.tunables_rx) -> msg => {
+                if let Ok(tunables) = msg {
+                  self.on_tunables(tunables);
+                }
+              }
+
+              recv(door_timer) -> _ => self.on_door_timer(Instant::now()),
+
+              recv(self.terminate_rx) -> _ => {
+                break;
+              }
+            }
+
+            self.publish_status();
+        }
+    }
+
+    fn on_hardware_event(&mut self, event: HardwareEvent) {
+        match event {
+            HardwareEvent::ButtonPressed { floor, kind } => self.on_request(floor, kind),
+            // requesting again lets the coordinator ask the peers for confirmation once more
+            HardwareEvent::ButtonRepressed { floor, kind } => {
+                debug!("Button {:?} at floor {} pressed again", kind, floor);
+                self.on_request(floor, kind);
+            }
+            HardwareEvent::ButtonReleased { .. } => {}
+            HardwareEvent::FloorReached(floor) => self.on_floor_arrival(floor),
+            HardwareEvent::Obstruction(is_obstructed) => self.on_obstruction(is_obstructed),
+            HardwareEvent::Stop(is_halted) => self.on_emergency_halt(is_halted),
+            HardwareEvent::HardwareUnavailable => self.on_hardware_reachable(false),
+            HardwareEvent::HardwareRestored => self.on_hardware_reachable(true),
+        }
+    }
+
+    fn on_request(&mut self, floor: u8, kind: ButtonKind) {
+        if floor >= self.num_floors {
+            warn!("Ignoring request for non-existent floor {}", floor);
+            return;
+        }
+
+        match kind.direction() {
+            // hall calls are only served once the coordinator has agreed on them with the peers
+            Some(direction) => {
+                let _ = self.hall_request_tx.send((floor, direction));
+            }
+            None => self.on_cab_request(floor),
+        }
+    }
+
+    fn on_cab_request(&mut self, floor: u8) {
+        // the light is already on for an active order, so there is nothing new to serve
+        let is_duplicate = self
+            .queue
+            .get_orders_for_floor(floor)
+            .iter()
+            .any(Order::is_command);
+        if is_duplicate {
+            self.set_button_light(floor, ButtonKind::Cab, true);
+            return;
+        }
+
+        let mut command = Command::new_with_expiration(floor, self.order_expiry_seconds);
+        command.claim(self.elevator_id);
+        if let Err(error) = self.queue.add_command(command) {
+            error!("Failed to queue cab request for floor {}: {}", floor, error);
+            return;
+        }
+        debug!("Accepted cab request for floor {}", floor);
+        self.set_button_light(floor, ButtonKind::Cab, true);
+        self.publish_cab_orders();
+        self.on_new_order(floor);
+    }
+
+    // Cab commands handed back by a peer after this node restarted without them
+    fn on_restored_commands(&mut self, commands: Vec<Command>) {
+        let mut restored_floors = Vec::new();
+        for command in commands {
+            let floor = command.target_floor;
+            let is_own = !matches!(command.claimed_by, Some(owner) if owner != self.elevator_id);
+            let is_known = self
+                .queue
+                .get_orders_for_floor(floor)
+                .iter()
+                .any(Order::is_command);
+            if floor >= self.num_floors || !is_own || is_known {
+                continue;
+            }
+
+            if let Err(error) = self.queue.add_command(command) {
+                error!("Failed to restore cab order for floor {}: {}", floor, error);
+                continue;
+            }
+            self.set_button_light(floor, ButtonKind::Cab, true);
+            restored_floors.push(floor);
+        }
+
+        if restored_floors.is_empty() {
+            return;
+        }
+        info!("Restored cab orders for floors {:?}", restored_floors);
+        self.publish_cab_orders();
+        for floor in restored_floors {
+            self.on_new_order(floor);
+        }
+    }
+
+    fn on_hall_call(&mut self, call: Call) {
+        if call.target_floor >= self.num_floors {
+            warn!(
+                "Ignoring hall call for non-existent floor {}",
+                call.target_floor
+            );
+            return;
+        }
+
+        let floor = call.target_floor;
+        let is_duplicate = self.queue.get_calls().iter().any(|active| {
+            active.id == call.id
+                || (active.target_floor == floor && active.direction == call.direction)
+        });
+        if is_duplicate {
+            return;
+        }
+
+        if let Err(error) = self.queue.add_call(call) {
+            error!("Failed to queue hall call for floor {}: {}", floor, error);
+            return;
+        }
+        debug!("Accepted hall call for floor {}", floor);
+        self.on_new_order(floor);
+    }
+
+    // A call handed back to the coordinator for another elevator to serve
+    fn on_hall_call_released(&mut self, call: Call) {
+        if self.queue.remove_order(call.id).is_some() {
+            debug!("Released hall call for floor {}", call.target_floor);
+        }
+    }
+
+    fn on_new_order(&mut self, floor: u8) {
+        if self.state == ElevatorState::Idle {
+            self.dispatch();
+        } else if self.state == ElevatorState::DoorOpen
+            && self.current_floor == Some(floor)
+            && self.clear_orders_at_floor(floor)
+        {
+            self.open_door();
+        }
+    }
+
+    fn on_tunables(&mut self, tunables: Tunables) {
+        let door_open_duration = Duration::from_millis(tunables.door_open_duration_milliseconds);
+        let door_stuck_timeout = Duration::from_millis(tunables.door_stuck_timeout_milliseconds);
+        self.door
+            .set_durations(door_open_duration, door_stuck_timeout);
+        // rebuilt even when unchanged, a scheduler may depend on the door durations
+        match scheduler::create_scheduler(
+            &tunables.scheduler,
+            self.num_floors,
+            door_open_duration,
+            door_stuck_timeout,
+        ) {
+            Some(scheduler) => {
+                if scheduler.name() != self.scheduler.name() {
+                    info!("Scheduler changed to {}", scheduler.name());
+                }
+                self.scheduler = scheduler;
+            }
+            None => warn!(
+                "Keeping the {} scheduler, {} is unknown",
+                self.scheduler.name(),
+                tunables.scheduler
+            ),
+        }
+        if tunables.halt_policy != self.halt_policy {
+            info!("Halt policy changed to {}", tunables.halt_policy);
+            self.halt_policy = tunables.halt_policy;
+        }
+        self.order_expiry_seconds = tunables.order_expiry_seconds;
+    }
+
+    fn on_floor_arrival(&mut self, floor: u8) {
+        self.current_floor = Some(floor);
+        self.is_between_floors = false;
+        self.last_floor_at = Some(Instant::now());
+        self.send_command(HardwareCommand::SetFloorIndicator(floor));
+
+        match self.state {
+            ElevatorState::Moving if self.should_stop(floor) => {
+                self.set_motor_direction(None);
+                self.clear_orders_at_floor(floor);
+                self.open_door();
+            }
+            ElevatorState::Idle => self.dispatch(),
+            // only the nearest floor policy keeps the motor running while halted
+            ElevatorState::Halted if self.is_motor_running => {
+                self.set_motor_direction(None);
+                self.hold_door_open();
+            }
+            _ => {}
+        }
+    }
+
+    fn on_obstruction(&mut self, is_obstructed: bool) {
+        if let Some(DoorEvent::Unstuck) = self.door.set_obstructed(is_obstructed, Instant::now()) {
+            info!("Door is no longer stuck");
+            self.update_availability();
+        }
+    }
+
+    fn on_emergency_halt(&mut self, is_halted: bool) {
+        if is_halted {
+            if self.state == ElevatorState::Halted {
+                return;
+            }
+
+            info!("Emergency halt engaged, {}", self.halt_policy);
+            self.state = ElevatorState::Halted;
+            self.send_command(HardwareCommand::SetStopLight(true));
+            self.update_availability();
+
+            match self.halt_policy {
+                HaltPolicy::StopAndKeepOrders => self.set_motor_direction(None),
+                HaltPolicy::StopAndClearCab => {
+                    self.set_motor_direction(None);
+                    self.clear_cab_orders();
+                }
+                HaltPolicy::NearestFloorOpenDoors => {
+                    // keep running until the next floor is reached
+                    if !self.is_motor_running {
+                        self.hold_door_open();
+                    }
+                }
+            }
+            return;
+        }
+
+        if self.state != ElevatorState::Halted {
+            return;
+        }
+
+        info!("Emergency halt released");
+        self.send_command(HardwareCommand::SetStopLight(false));
+        if self.door.is_open() {
+            self.open_door();
+        } else if self.is_motor_running {
+            self.state = ElevatorState::Moving;
+        } else {
+            self.state = ElevatorState::Idle;
+            self.dispatch();
+        }
+        self.update_availability();
+    }
+
+    // The driver lost or regained the elevator server, the outputs sent meanwhile are kept by
+    // the driver, so only the hall calls have to go elsewhere
+    fn on_hardware_reachable(&mut self, is_reachable: bool) {
+        if is_reachable {
+            info!("Elevator hardware is reachable again");
+        } else {
+            warn!("Elevator hardware is unreachable, marking elevator unavailable");
+        }
+        self.is_hardware_reachable = is_reachable;
+        self.update_availability();
+    }
+
+    fn on_door_timer(&mut self, now: Instant) {
+        match self.door.poll(now) {
+            Some(DoorEvent::TimedOut) => self.on_door_timeout(),
+            Some(DoorEvent::Stuck) => {
+                warn!("Door has been obstructed for too long, marking elevator unavailable");
+                self.update_availability();
+            }
+            _ => {}
+        }
+    }
+
+    fn on_door_timeout(&mut self) {
+        if self.state != ElevatorState::DoorOpen {
+            return;
+        }
+
+        // announce a change of direction by serving the opposite call with another door cycle
+        if let Some(floor) = self.current_floor {
+            if self.clear_orders_at_floor(floor) {
+                self.open_door();
+                return;
+            }
+        }
+
+        self.close_door();
+        self.state = ElevatorState::Idle;
+        self.dispatch();
+    }
+
+    // Pick the next target from the queue and start serving it
+    fn dispatch(&mut self) {
+        // nothing is scheduled until homing has reached a floor
+        let Some(floor) = self.current_floor else {
+            return;
+        };
+
+        if self.queue.is_empty() {
+            self.direction = None;
+            return;
+        }
+
+        let context = self.scheduler_context(floor);
+        let Some(next) = self
+            .queue
+            .get_scheduled_orders(&self.scheduler, &context)
+            .into_iter()
+            .next()
+        else {
+            return;
+        };
+
+        let target = next.target_floor();
+        let direction = if target > floor {
+            Direction::Up
+        } else if target < floor {
+            Direction::Down
+        } else if self.is_between_floors {
+            // stopped just past the last known floor, so head back to it
+            match self.direction {
+                Some(Direction::Up) => Direction::Down,
+                _ => Direction::Up,
+            }
+        } else {
+            self.direction = next.direction().or(self.direction);
+            self.clear_orders_at_floor(floor);
+            self.open_door();
+            return;
+        };
+
+        debug!("Heading {:?} towards floor {}", direction, target);
+        self.direction = Some(direction);
+        self.state = ElevatorState::Moving;
+        self.is_between_floors = true;
+        self.set_motor_direction(Some(direction));
+    }
+
+    // Everything the scheduler gets to know about this elevator and its peers
+    fn scheduler_context(&self, floor: u8) -> SchedulerContext {
+        SchedulerContext {
+            is_door_open: self.door.is_open(),
+            is_obstructed: self.door.is_obstructed(),
+            is_halted: self.state == ElevatorState::Halted,
+            is_between_floors: self.is_between_floors,
+            time_since_last_floor: self
+                .last_floor_at
+                .map_or(Duration::ZERO, |reached_at| reached_at.elapsed()),
+            peers: self.peer_states.clone(),
+            ..SchedulerContext::new(floor, self.direction, self.elevator_id, self.num_floors)
+        }
+    }
+
+    fn should_stop(&self, floor: u8) -> bool {
+        let Some(direction) = self.direction else {
+            return true;
+        };
+
+        let at_end = match direction {
+            Direction::Up => floor + 1 >= self.num_floors,
+            Direction::Down => floor == 0,
+        };
+
+        at_end
+            || !self.has_orders_ahead(floor, direction)
+            || self
+                .queue
+                .get_orders_for_floor(floor)
+                .iter()
+                .any(|order| order.direction().is_none() || order.direction() == Some(direction))
+    }
+
+    fn has_orders_ahead(&self, floor: u8, direction: Direction) -> bool {
+        self.queue.get_orders().iter().any(|order| match direction {
+            Direction::Up => order.target_floor() > floor,
+            Direction::Down => order.target_floor() < floor,
+        })
+    }
+
+    // Clear the orders served by stopping at this floor, returns whether anything was cleared
+    // Only calls matching the announced direction are served, so the opposite call stays lit
+    // until the elevator has no reason to keep going the way it announced.
+    fn clear_orders_at_floor(&mut self, floor: u8) -> bool {
+        let orders = self.queue.get_orders_for_floor(floor);
+
+        let keeps_direction = self.direction.is_some_and(|direction| {
+            self.has_orders_ahead(floor, direction)
+                || orders
+                    .iter()
+                    .any(|order| order.direction() == Some(direction))
+        });
+        if !keeps_direction {
+            if let Some(direction) = orders.iter().find_map(Order::direction) {
+                self.direction = Some(direction);
+            }
+        }
+
+        let mut is_cleared = false;
+        let mut is_command_cleared = false;
+        for order in orders {
+            if order.direction().is_none() || order.direction() == self.direction {
+                self.queue.remove_order(order.id());
+                match order {
+                    Order::Command(_) => {
+                        self.set_button_light(floor, ButtonKind::Cab, false);
+                        is_command_cleared = true;
+                    }
+                    // the coordinator turns the hall light off once peers know it is served
+                    Order::Call(call) => {
+                        let _ = self.hall_call_served_tx.send(call);
+                    }
+                }
+                is_cleared = true;
+            }
+        }
+        if is_command_cleared {
+            self.publish_cab_orders();
+        }
+        is_cleared
+    }
+
+    // Light the cab buttons of commands restored from the journal
+    fn restore_cab_lights(&mut self) {
+        let commands = self.queue.get_commands();
+        if !commands.is_empty() {
+            info!("Restored {} cab orders from the journal", commands.len());
+        }
+        for command in commands {
+            self.set_button_light(command.target_floor, ButtonKind::Cab, true);
+        }
+    }
+
+    fn clear_cab_orders(&mut self) {
+        for command in self.queue.get_commands() {
+            self.queue.remove_order(command.id);
+            self.set_button_light(command.target_floor, ButtonKind::Cab, false);
+        }
+        self.publish_cab_orders();
+    }
+
+    // Share what the elevator is doing with the coordinator, which assigns the hall calls
+    // Nothing is known before homing has reached a floor, and unchanged states are not resent.
+    fn publish_status(&mut self) {
+        let Some(floor) = self.current_floor else {
+            return;
+        };
+        let status = ElevatorStatus {
+            floor,
+            direction: self.direction,
+            behaviour: self.state,
+            commands: self.queue.get_commands(),
+            calls: self.queue.get_calls(),
+        };
+        if self.published_status.as_ref() != Some(&status) {
+            let _ = self.status_tx.send(status.clone());
+            self.published_status = Some(status);
+        }
+    }
+
+    // Share the current cab commands so the peers can keep a backup
+    fn publish_cab_orders(&self) {
+        let _ = self.cab_orders_tx.send(self.queue.get_commands());
+    }
+
+    fn open_door(&mut self) {
+        self.state = ElevatorState::DoorOpen;
+        self.hold_door_open();
+    }
+
+    // Open the door, or restart its countdown, without leaving the current state
+    fn hold_door_open(&mut self) {
+        self.door.open(Instant::now());
+        self.send_command(HardwareCommand::SetDoorLight(true));
+    }
+
+    fn close_door(&mut self) {
+        self.door.close();
+        self.send_command(HardwareCommand::SetDoorLight(false));
+    }
+
+    fn update_availability(&mut self) {
+        let is_available = self.is_hardware_reachable
+            && !self.door.is_stuck()
+            && self.state != ElevatorState::Halted;
+        if self.is_available != is_available {
+            self.is_available = is_available;
+            let _ = self.availability_tx.send(is_available);
+        }
+    }
+
+    fn set_motor_direction(&mut self, direction: Option<Direction>) {
+        self.is_motor_running = direction.is_some();
+        self.send_command(HardwareCommand::SetMotor(direction));
+    }
+
+    fn set_button_light(&self, floor: u8, kind: ButtonKind, is_lit: bool) {
+        self.send_command(HardwareCommand::SetButtonLight {
+            floor,
+            kind,
+            is_lit,
+        });
+    }
+
+    fn send_command(&self, command: HardwareCommand) {
+        let _ = self.hw_command_tx.send(command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{init_clock_with_random_id, TimestampExt};
+    use crate::config::Config;
+    use crate::queue::scheduler::FifoScheduler;
+    use std::cell::RefCell;
+
+    struct TestHarness {
+        fsm: ElevatorFsm,
+        command_rx: channel::Receiver<HardwareCommand>,
+        pending_commands: RefCell<Vec<HardwareCommand>>, // received but not yet checked
+        hall_request_rx: channel::Receiver<(u8, Direction)>,
+        hall_call_served_rx: channel::Receiver<Call>,
+        cab_orders_rx: channel::Receiver<Vec<Command>>,
+        availability_rx: channel::Receiver<bool>,
+        status_rx: channel::Receiver<ElevatorStatus>,
+    }
+
+    fn test_config(num_floors: u8) -> HardwareConfig {
+        HardwareConfig {
+            num_floors,
+            driver_address: "localhost".to_string(),
+            driver_port: 15657,
+            driver_channel_poll_timeout_milliseconds: 10,
+            button_debounce_milliseconds: 0,
+            door_open_duration_milliseconds: 3000,
+            door_stuck_timeout_milliseconds: 10000,
+            homing_timeout_milliseconds: 10000,
+            halt_policy: HaltPolicy::StopAndKeepOrders,
+            order_expiry_seconds: 60,
+        }
+    }
+
+    fn setup_fsm(num_floors: u8) -> TestHarness {
+        setup_fsm_with_config(&test_config(num_floors))
+    }
+
+    fn setup_fsm_with_config(config: &HardwareConfig) -> TestHarness {
+        let _ = init_clock_with_random_id();
+
+        let (command_tx, command_rx) = channel::unbounded();
+        let (_, event_rx) = channel::unbounded();
+        let (hall_request_tx, hall_request_rx) = channel::unbounded();
+        let (_, hall_call_rx) = channel::unbounded();
+        let (_, hall_call_released_rx) = channel::unbounded();
+        let (hall_call_served_tx, hall_call_served_rx) = channel::unbounded();
+        let (cab_orders_tx, cab_orders_rx) = channel::unbounded();
+        let (_, restored_commands_rx) = channel::unbounded();
+        let (_, tunables_rx) = channel::unbounded();
+        let (availability_tx, availability_rx) = channel::unbounded();
+        let (status_tx, status_rx) = channel::unbounded();
+        let (_, peer_states_rx) = channel::unbounded();
+        let (_, terminate_rx) = channel::unbounded();
+
+        let fsm = ElevatorFsm::new(
+            config,
+            Uuid::new_v4(),
+            Box::new(FifoScheduler),
+            OrderQueue::new(),
+            command_tx,
+            event_rx,
+            hall_request_tx,
+            hall_call_rx,
+            hall_call_released_rx,
+            hall_call_served_tx,
+            cab_orders_tx,
+            restored_commands_rx,
+            tunables_rx,
+            availability_tx,
+            status_tx,
+            peer_states_rx,
+            terminate_rx,
+        );
+
+        TestHarness {
+            fsm,
+            command_rx,
+            pending_commands: RefCell::new(Vec::new()),
+            hall_request_rx,
+            hall_call_served_rx,
+            cab_orders_rx,
+            availability_rx,
+            status_rx,
+        }
+    }
+
+    impl TestHarness {
+        // Take the commands sent so far that the selector picks, keeping the others
+        fn take<T>(&self, select: impl Fn(&HardwareCommand) -> Option<T>) -> Vec<T> {
+            let mut pending = self.pending_commands.borrow_mut();
+            pending.extend(self.command_rx.try_iter());
+            let mut taken = Vec::new();
+            pending.retain(|command| match select(command) {
+                Some(value) => {
+                    taken.push(value);
+                    false
+                }
+                None => true,
+            });
+            taken
+        }
+
+        fn motor(&self) -> Vec<Option<Direction>> {
+            self.take(|command| match command {
+                HardwareCommand::SetMotor(direction) => Some(*direction),
+                _ => None,
+            })
+        }
+
+        fn button_lights(&self) -> Vec<(u8, ButtonKind, bool)> {
+            self.take(|command| match command {
+                HardwareCommand::SetButtonLight {
+                    floor,
+                    kind,
+                    is_lit,
+                } => Some((*floor, *kind, *is_lit)),
+                _ => None,
+            })
+        }
+
+        fn door_lights(&self) -> Vec<bool> {
+            self.take(|command| match command {
+                HardwareCommand::SetDoorLight(is_lit) => Some(*is_lit),
+                _ => None,
+            })
+        }
+
+        fn floor_indicators(&self) -> Vec<u8> {
+            self.take(|command| match command {
+                HardwareCommand::SetFloorIndicator(floor) => Some(*floor),
+                _ => None,
+            })
+        }
+
+        fn stop_lights(&self) -> Vec<bool> {
+            self.take(|command| match command {
+                HardwareCommand::SetStopLight(is_lit) => Some(*is_lit),
+                _ => None,
+            })
+        }
+    }
+
+    fn served_floors(harness: &TestHarness) -> Vec<(u8, Direction)> {
+        harness
+            .hall_call_served_rx
+            .try_iter()
+            .map(|call| (call.target_floor, call.direction))
+            .collect()
+    }
+
+    fn drain<T>(rx: &channel::Receiver<T>) -> Vec<T> {
+        rx.try_iter().collect()
+    }
+
+    #[test]
+    fn test_cab_request_moves_and_stops_at_target() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+
+        harness.fsm.on_request(2, ButtonKind::Cab);
+        assert_eq!(harness.fsm.state(), ElevatorState::Moving);
+        assert_eq!(harness.motor(), vec![Some(Direction::Up)]);
+        assert_eq!(harness.button_lights(), vec![(2, ButtonKind::Cab, true)]);
+
+        // passing floor 1 without orders there
+        harness.fsm.on_floor_arrival(1);
+        assert_eq!(harness.fsm.state(), ElevatorState::Moving);
+        assert!(harness.motor().is_empty());
+
+        harness.fsm.on_floor_arrival(2);
+        assert_eq!(harness.fsm.state(), ElevatorState::DoorOpen);
+        assert_eq!(harness.motor(), vec![None]);
+        assert_eq!(harness.door_lights(), vec![true]);
+        assert_eq!(harness.button_lights(), vec![(2, ButtonKind::Cab, false)]);
+        assert_eq!(harness.floor_indicators(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_restored_cab_orders_are_lit_and_served() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.queue.add_command(Command::new(3)).unwrap();
+
+        harness.fsm.restore_cab_lights();
+        assert_eq!(harness.button_lights(), vec![(3, ButtonKind::Cab, true)]);
+
+        // serving starts as soon as the elevator knows where it is
+        harness.fsm.on_floor_arrival(1);
+        assert_eq!(harness.fsm.state(), ElevatorState::Moving);
+        assert_eq!(harness.motor(), vec![Some(Direction::Up)]);
+    }
+
+    #[test]
+    fn test_cab_orders_are_published_for_backup() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+
+        harness.fsm.on_request(1, ButtonKind::Cab);
+        let published = drain(&harness.cab_orders_rx);
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0][0].target_floor, 1);
+        assert_eq!(published[0][0].claimed_by, Some(harness.fsm.elevator_id));
+
+        harness.fsm.on_floor_arrival(1);
+        assert_eq!(drain(&harness.cab_orders_rx), vec![Vec::new()]);
+    }
+
+    #[test]
+    fn test_restored_commands_from_peers_are_lit_once() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+        harness.fsm.on_request(2, ButtonKind::Cab);
+        harness.button_lights();
+        drain(&harness.cab_orders_rx);
+
+        let mut foreign = Command::new(1);
+        foreign.claim(Uuid::new_v4());
+        let mut own = Command::new(3);
+        own.claim(harness.fsm.elevator_id);
+        // the peer's backup still holds the command for floor 2 that is already queued
+        harness
+            .fsm
+            .on_restored_commands(vec![Command::new(2), foreign, own]);
+
+        assert_eq!(harness.button_lights(), vec![(3, ButtonKind::Cab, true)]);
+        assert_eq!(harness.fsm.queue.count_commands(), 2);
+        assert_eq!(drain(&harness.cab_orders_rx).len(), 1);
+    }
+
+    #[test]
+    fn test_tunables_apply_to_next_door_cycle() {
+        let mut config = test_config(4);
+        config.halt_policy = HaltPolicy::StopAndKeepOrders;
+        let mut harness = setup_fsm_with_config(&config);
+        harness.fsm.on_floor_arrival(0);
+
+        let mut tunables = Config::default().tunables();
+        tunables.door_open_duration_milliseconds = 500;
+        tunables.halt_policy = HaltPolicy::StopAndClearCab;
+        tunables.order_expiry_seconds = 5;
+        tunables.scheduler = "nearest".to_string();
+        harness.fsm.on_tunables(tunables);
+        assert_eq!(harness.fsm.scheduler.name(), "nearest");
+
+        let opened_at = Instant::now();
+        harness.fsm.on_request(0, ButtonKind::Cab);
+        let deadline = harness.fsm.door.next_deadline().unwrap();
+        assert!(deadline <= opened_at + Duration::from_secs(1));
+        assert_eq!(harness.fsm.halt_policy, HaltPolicy::StopAndClearCab);
+
+        harness.fsm.on_request(3, ButtonKind::Cab);
+        let command = &harness.fsm.queue.get_commands()[0];
+        assert_eq!(
+            command.expires_at,
+            command.created_at.add_duration(Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn test_released_calls_are_dropped() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+        let call = Call::new(2, Direction::Up);
+        harness.fsm.on_hall_call(call.clone());
+        harness.fsm.on_request(3, ButtonKind::Cab);
+
+        harness.fsm.on_hall_call_released(call);
+        assert_eq!(harness.fsm.queue.count_calls(), 0);
+        assert_eq!(harness.fsm.queue.count_commands(), 1);
+
+        // the elevator passes the floor of the released call without stopping
+        harness.fsm.on_floor_arrival(2);
+        assert!(served_floors(&harness).is_empty());
+        assert_eq!(harness.fsm.state(), ElevatorState::Moving);
+    }
+
+    #[test]
+    fn test_repress_requests_again() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+
+        harness.fsm.on_hardware_event(HardwareEvent::ButtonPressed {
+            floor: 2,
+            kind: ButtonKind::HallDown,
+        });
+        harness
+            .fsm
+            .on_hardware_event(HardwareEvent::ButtonReleased {
+                floor: 2,
+                kind: ButtonKind::HallDown,
+            });
+        harness
+            .fsm
+            .on_hardware_event(HardwareEvent::ButtonRepressed {
+                floor: 2,
+                kind: ButtonKind::HallDown,
+            });
+        assert_eq!(
+            drain(&harness.hall_request_rx),
+            vec![(2, Direction::Down), (2, Direction::Down)]
+        );
+
+        // a lit cab button is only lit again, the order is not duplicated
+        harness.fsm.on_hardware_event(HardwareEvent::ButtonPressed {
+            floor: 3,
+            kind: ButtonKind::Cab,
+        });
+        harness
+            .fsm
+            .on_hardware_event(HardwareEvent::ButtonRepressed {
+                floor: 3,
+                kind: ButtonKind::Cab,
+            });
+        assert_eq!(
+            harness.button_lights(),
+            vec![(3, ButtonKind::Cab, true), (3, ButtonKind::Cab, true)]
+        );
+        assert_eq!(harness.fsm.queue.count_commands(), 1);
+    }
+
+    #[test]
+    fn test_hall_request_is_forwarded_to_coordinator() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(1);
+
+        harness.fsm.on_request(3, ButtonKind::HallUp);
+        assert_eq!(drain(&harness.hall_request_rx), vec![(3, Direction::Up)]);
+        assert_eq!(harness.fsm.state(), ElevatorState::Idle);
+        assert!(harness.button_lights().is_empty());
+        assert!(harness.fsm.queue.is_empty());
+    }
+
+    #[test]
+    fn test_hall_call_at_current_floor_opens_door() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(1);
+
+        harness.fsm.on_hall_call(Call::new(1, Direction::Down));
+        assert_eq!(harness.fsm.state(), ElevatorState::DoorOpen);
+        assert!(harness.motor().is_empty());
+        assert_eq!(harness.door_lights(), vec![true]);
+        assert_eq!(served_floors(&harness), vec![(1, Direction::Down)]);
+
+        harness.fsm.on_door_timeout();
+        assert_eq!(harness.fsm.state(), ElevatorState::Idle);
+        assert_eq!(harness.door_lights(), vec![false]);
+    }
+
+    #[test]
+    fn test_door_stays_open_while_obstructed() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+        harness.fsm.on_request(0, ButtonKind::Cab);
+        harness.fsm.on_obstruction(true);
+
+        let start = Instant::now();
+        harness.fsm.on_door_timer(start + Duration::from_secs(5));
+        assert_eq!(harness.fsm.state(), ElevatorState::DoorOpen);
+
+        harness.fsm.on_obstruction(false);
+        harness
+            .fsm
+            .on_door_timer(Instant::now() + Duration::from_secs(3));
+        assert_eq!(harness.fsm.state(), ElevatorState::Idle);
+        assert_eq!(harness.door_lights(), vec![true, false]);
+    }
+
+    #[test]
+    fn test_scheduler_context_describes_the_elevator() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(1);
+        harness.fsm.on_request(1, ButtonKind::Cab);
+        harness.fsm.on_obstruction(true);
+
+        let context = harness.fsm.scheduler_context(1);
+        assert_eq!(context.num_floors, 4);
+        assert!(context.is_door_open && context.is_obstructed);
+        assert!(!context.is_halted && !context.is_between_floors);
+
+        harness.fsm.on_obstruction(false);
+        harness
+            .fsm
+            .on_door_timer(Instant::now() + Duration::from_secs(3));
+        harness.fsm.on_request(3, ButtonKind::Cab);
+        harness.fsm.on_emergency_halt(true);
+        let context = harness.fsm.scheduler_context(1);
+        assert!(!context.is_door_open && !context.is_obstructed);
+        assert!(context.is_halted && context.is_between_floors);
+        assert_eq!(context.current_direction, Some(Direction::Up));
+    }
+
+    #[test]
+    fn test_stuck_door_marks_elevator_unavailable() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+        harness.fsm.on_request(0, ButtonKind::Cab);
+        harness.fsm.on_obstruction(true);
+
+        harness
+            .fsm
+            .on_door_timer(Instant::now() + Duration::from_secs(10));
+        assert!(!harness.fsm.is_available());
+        assert_eq!(harness.fsm.state(), ElevatorState::DoorOpen);
+        assert_eq!(drain(&harness.availability_rx), vec![false]);
+
+        harness.fsm.on_obstruction(false);
+        assert!(harness.fsm.is_available());
+        assert_eq!(drain(&harness.availability_rx), vec![true]);
+    }
+
+    #[test]
+    fn test_unreachable_hardware_marks_elevator_unavailable() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+
+        harness
+            .fsm
+            .on_hardware_event(HardwareEvent::HardwareUnavailable);
+        assert!(!harness.fsm.is_available());
+        // orders are still taken, the driver applies them once reconnected
+        harness.fsm.on_request(2, ButtonKind::Cab);
+        assert_eq!(harness.fsm.state(), ElevatorState::Moving);
+
+        harness
+            .fsm
+            .on_hardware_event(HardwareEvent::HardwareRestored);
+        assert!(harness.fsm.is_available());
+        assert_eq!(drain(&harness.availability_rx), vec![false, true]);
+    }
+
+    #[test]
+    fn test_status_is_published_on_change() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.publish_status();
+        assert!(drain(&harness.status_rx).is_empty());
+
+        harness.fsm.on_floor_arrival(1);
+        harness.fsm.on_request(3, ButtonKind::Cab);
+        harness.fsm.publish_status();
+        harness.fsm.publish_status();
+
+        let published = drain(&harness.status_rx);
+        assert_eq!(published.len(), 1);
+        let status = &published[0];
+        assert_eq!(status.floor, 1);
+        assert_eq!(status.direction, Some(Direction::Up));
+        assert_eq!(status.behaviour, ElevatorState::Moving);
+        assert_eq!(status.commands.len(), 1);
+        assert!(status.calls.is_empty());
+    }
+
+    #[test]
+    fn test_opposite_calls_are_cleared_one_at_a_time() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+
+        harness.fsm.on_hall_call(Call::new(2, Direction::Up));
+        harness.fsm.on_hall_call(Call::new(2, Direction::Down));
+
+        harness.fsm.on_floor_arrival(1);
+        harness.fsm.on_floor_arrival(2);
+        assert_eq!(harness.fsm.state(), ElevatorState::DoorOpen);
+        assert_eq!(served_floors(&harness), vec![(2, Direction::Up)]);
+
+        // nothing above, so the direction change is announced with another door cycle
+        harness.fsm.on_door_timeout();
+        assert_eq!(harness.fsm.state(), ElevatorState::DoorOpen);
+        assert_eq!(served_floors(&harness), vec![(2, Direction::Down)]);
+
+        harness.fsm.on_door_timeout();
+        assert_eq!(harness.fsm.state(), ElevatorState::Idle);
+    }
+
+    #[test]
+    fn test_emergency_halt_stops_and_resumes() {
+        let mut harness = setup_fsm(4);
+        harness.fsm.on_floor_arrival(0);
+        harness.fsm.on_request(3, ButtonKind::Cab);
+        harness.motor();
+
+        harness.fsm.on_emergency_halt(true);
+        assert_eq!(harness.fsm.state(), ElevatorState::Halted);
+        assert!(!harness.fsm.is_available());
+        assert_eq!(harness.motor(), vec![None]);
+        assert_eq!(harness.stop_lights(), vec![true]);
+        assert_eq!(drain(&harness.availability_rx), vec![false]);
+
+        // requests are still accepted while halted
+        harness.fsm.on_request(1, ButtonKind::Cab);
+        assert!(harness.motor().is_empty());
+
+        harness.fsm.on_emergency_halt(false);
+        assert_eq!(harness.fsm.state(), ElevatorState::Moving);
+        assert!(harness.fsm.is_available());
+        assert_eq!(harness.motor(), vec![Some(Direction::Up)]);
+        assert_eq!(harness.stop_lights(), vec![false]);
+        assert_eq!(drain(&harness.availability_rx), vec![true]);
+    }
+
+    #[test]
+    fn test_emergency_halt_clears_cab_orders() {
+        let mut config = test_config(4);
+        config.halt_policy = HaltPolicy::StopAndClearCab;
+        let mut harness = setup_fsm_with_config(&config);
+        harness.fsm.on_floor_arrival(0);
+        harness.fsm.on_request(3, ButtonKind::Cab);
+        harness.fsm.on_hall_call(Call::new(2, Direction::Down));
+        harness.button_lights();
+
+        harness.fsm.on_emergency_halt(true);
+        assert_eq!(harness.button_lights(), vec![(3, ButtonKind::Cab, false)]);
+        assert_eq!(harness.fsm.queue.count_commands(), 0);
+        assert_eq!(harness.fsm.queue.count_calls(), 1);
+    }
+
+    #[test]
+    fn test_emergency_halt_continues_to_nearest_floor() {
+        let mut config = test_config(4);
+        config.halt_policy = HaltPolicy::NearestFloorOpenDoors;
+        let mut harness = setup_fsm_with_config(&config);
+        harness.fsm.on_floor_arrival(0);
+        harness.fsm.on_request(3, ButtonKind::Cab);
+        harness.motor();
+
+        harness.fsm.on_emergency_halt(true);
+        assert!(harness.motor().is_empty());
+
+        harness.fsm.on_floor_arrival(1);
+        assert_eq!(harness.fsm.state(), ElevatorState::Halted);
+        assert_eq!(harness.motor(), vec![None]);
+        assert_eq!(harness.door_lights(), vec![true]);
+
+        // the door stays open for as long as the elevator is halted
+        harness
+            .fsm
+            .on_door_timer(Instant::now() + Duration::from_secs(5));
+        assert_eq!(harness.fsm.state(), ElevatorState::Halted);
+        assert!(harness.door_lights().is_empty());
+
+        harness.fsm.on_emergency_halt(false);
+        assert_eq!(harness.fsm.state(), ElevatorState::DoorOpen);
+    }
+}
@@ -1,168 +1,762 @@
-use std::time::Duration;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel as channel;
 
-use crate::config::HardwareConfig;
+use super::button::ButtonDebouncer;
+use super::elevio_client::ElevioClient;
+use super::events::{ButtonKind, HardwareCommand, HardwareEvent};
+use super::homing::{Homing, HomingStep};
+use super::io::ElevatorIo;
+use super::metrics::DriverMetrics;
+use crate::config::{HardwareConfig, Tunables};
+use crate::queue::Direction;
 
-use driver_rust::elevio::elev::Elevator;
-use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+use driver_rust::elevio::elev::{DIRN_DOWN, DIRN_STOP, DIRN_UP};
 
-use log::{error, info};
+use log::{debug, error, info, warn};
 
-const NUM_CALL_VARIANTS: usize = 3;
+const NUM_CALL_VARIANTS: usize = ButtonKind::ALL.len();
 
-pub struct ElevatorDriver {
-    elevator: Elevator,
-    thread_sleep_time: u64,
+// Capacity of the bounded channels between the driver and the state machine
+pub const HARDWARE_CHANNEL_CAPACITY: usize = 64;
+
+// How often the input poller logs its metrics
+const METRICS_LOG_INTERVAL: Duration = Duration::from_secs(30);
+
+// Wait before the first reconnect attempt, doubled after every failed attempt up to the max
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_millis(100);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+// Outputs as last requested, applied again once a lost elevator server is back
+#[derive(Debug)]
+struct Outputs {
+    motor_direction: u8,
+    buttons: Vec<[bool; NUM_CALL_VARIANTS]>, // a press on a lit button is a re-press
+    floor_indicator: Option<u8>,
+    is_door_lit: bool,
+    is_stop_lit: bool,
+}
+
+type SharedOutputs = Arc<Mutex<Outputs>>;
+
+impl Outputs {
+    // Everything off and the motor stopped
+    fn new(num_floors: u8) -> Self {
+        Self {
+            motor_direction: DIRN_STOP,
+            buttons: vec![[false; NUM_CALL_VARIANTS]; num_floors as usize],
+            floor_indicator: None,
+            is_door_lit: false,
+            is_stop_lit: false,
+        }
+    }
+
+    fn is_lit(&self, floor: u8, kind: ButtonKind) -> bool {
+        self.buttons
+            .get(floor as usize)
+            .map(|lights| lights[kind.call_type() as usize])
+            .unwrap_or(false)
+    }
+
+    // Remember the command, then write it
+    fn apply(&mut self, elevator: &impl ElevatorIo, command: HardwareCommand) -> io::Result<()> {
+        match command {
+            HardwareCommand::SetMotor(direction) => {
+                self.motor_direction = match direction {
+                    Some(Direction::Up) => DIRN_UP,
+                    Some(Direction::Down) => DIRN_DOWN,
+                    None => DIRN_STOP,
+                };
+                elevator.motor_direction(self.motor_direction)
+            }
+            HardwareCommand::SetButtonLight {
+                floor,
+                kind,
+                is_lit,
+            } => {
+                if let Some(lights) = self.buttons.get_mut(floor as usize) {
+                    lights[kind.call_type() as usize] = is_lit;
+                }
+                elevator.call_button_light(floor, kind.call_type(), is_lit)
+            }
+            HardwareCommand::SetFloorIndicator(floor) => {
+                self.floor_indicator = Some(floor);
+                elevator.floor_indicator(floor)
+            }
+            HardwareCommand::SetDoorLight(is_lit) => {
+                self.is_door_lit = is_lit;
+                elevator.door_light(is_lit)
+            }
+            HardwareCommand::SetStopLight(is_lit) => {
+                self.is_stop_lit = is_lit;
+                elevator.stop_button_light(is_lit)
+            }
+        }
+    }
+
+    // Write every output, bringing a fresh elevator server up to date
+    fn restore(&self, elevator: &impl ElevatorIo) -> io::Result<()> {
+        elevator.motor_direction(self.motor_direction)?;
+        for (floor, lights) in self.buttons.iter().enumerate() {
+            for kind in ButtonKind::ALL {
+                elevator.call_button_light(
+                    floor as u8,
+                    kind.call_type(),
+                    lights[kind.call_type() as usize],
+                )?;
+            }
+        }
+        if let Some(floor) = self.floor_indicator {
+            elevator.floor_indicator(floor)?;
+        }
+        elevator.door_light(self.is_door_lit)?;
+        elevator.stop_button_light(self.is_stop_lit)
+    }
+}
+
+fn lock(outputs: &SharedOutputs) -> MutexGuard<'_, Outputs> {
+    outputs.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+// Drives the elevator from two threads: an input poller and an output worker
+// The poller samples every input on a fixed interval and publishes the changes, while the
+// worker applies the outputs as soon as they arrive, so neither waits on the other. Generic
+// over the IO so it can run against anything implementing ElevatorIo, the elevator server
+// reached over TCP being the default. A lost connection is reported as HardwareUnavailable
+// and retried with backoff, the outputs requested meanwhile are kept and applied on reconnect.
+pub struct ElevatorDriver<E: ElevatorIo = ElevioClient> {
+    homing_timeout: u64,
+    input: InputPoller<E>,
+    output: OutputWorker<E>,
+    metrics: Arc<DriverMetrics>,
+}
+
+// Samples the inputs and publishes their changes
+struct InputPoller<E: ElevatorIo> {
+    elevator: E,
+    poll_interval: Duration,
     current_floor: u8,
+    is_at_floor: bool,
     is_halted: bool,
     is_obstructed: bool,
-    requests: Vec<Vec<bool>>,
-    hw_motor_direction_rx: channel::Receiver<u8>,
-    hw_button_light_rx: channel::Receiver<(u8, u8, bool)>,
-    hw_request_tx: channel::Sender<(u8, u8)>,
-    hw_floor_sensor_tx: channel::Sender<u8>,
-    hw_floor_indicator_rx: channel::Receiver<u8>,
-    hw_door_light_rx: channel::Receiver<bool>,
-    hw_emergency_halt_tx: channel::Sender<bool>,
-    hw_obstruction_tx: channel::Sender<bool>,
+    buttons: ButtonDebouncer,
+    outputs: SharedOutputs,
+    metrics: Arc<DriverMetrics>,
+    hw_event_tx: channel::Sender<HardwareEvent>,
+    tunables_rx: channel::Receiver<Tunables>,
     terminate_rx: channel::Receiver<()>,
 }
 
-impl ElevatorDriver {
+// Applies the outputs requested by the state machine and the coordinator
+struct OutputWorker<E: ElevatorIo> {
+    elevator: E,
+    outputs: SharedOutputs,
+    metrics: Arc<DriverMetrics>,
+    hw_command_rx: channel::Receiver<HardwareCommand>,
+}
+
+impl ElevatorDriver<ElevioClient> {
+    // Connect to the elevator server given in the configuration
     pub fn new(
         config: &HardwareConfig,
-        hw_motor_direction_rx: channel::Receiver<u8>,
-        hw_button_light_rx: channel::Receiver<(u8, u8, bool)>,
-        hw_request_tx: channel::Sender<(u8, u8)>,
-        hw_floor_sensor_tx: channel::Sender<u8>,
-        hw_floor_indicator_rx: channel::Receiver<u8>,
-        hw_door_light_rx: channel::Receiver<bool>,
-        hw_emergency_halt_tx: channel::Sender<bool>,
-        hw_obstruction_tx: channel::Sender<bool>,
+        hw_event_tx: channel::Sender<HardwareEvent>,
+        hw_command_rx: channel::Receiver<HardwareCommand>,
+        tunables_rx: channel::Receiver<Tunables>,
         terminate_rx: channel::Receiver<()>,
     ) -> Result<ElevatorDriver, std::io::Error> {
-        let elev = Elevator::init(
+        let elev = ElevioClient::connect(
             &format!("{}:{}", config.driver_address, config.driver_port)[..],
             config.num_floors,
         )?;
-        Ok(ElevatorDriver {
-            elevator: elev,
-            thread_sleep_time: config.driver_channel_poll_timeout_milliseconds,
-            current_floor: u8::MAX, // because unknown starting position
-            is_halted: false,
-            is_obstructed: false,
-            requests: vec![vec![false; NUM_CALL_VARIANTS]; config.num_floors as usize],
-            hw_motor_direction_rx,
-            hw_button_light_rx,
-            hw_request_tx,
-            hw_floor_sensor_tx,
-            hw_floor_indicator_rx,
-            hw_door_light_rx,
-            hw_emergency_halt_tx,
-            hw_obstruction_tx,
+        Ok(ElevatorDriver::with_io(
+            elev,
+            config,
+            hw_event_tx,
+            hw_command_rx,
+            tunables_rx,
             terminate_rx,
-        })
+        ))
+    }
+}
+
+impl<E: ElevatorIo + Clone + Send + 'static> ElevatorDriver<E> {
+    pub fn with_io(
+        elevator: E,
+        config: &HardwareConfig,
+        hw_event_tx: channel::Sender<HardwareEvent>,
+        hw_command_rx: channel::Receiver<HardwareCommand>,
+        tunables_rx: channel::Receiver<Tunables>,
+        terminate_rx: channel::Receiver<()>,
+    ) -> ElevatorDriver<E> {
+        let outputs = Arc::new(Mutex::new(Outputs::new(config.num_floors)));
+        let metrics = Arc::new(DriverMetrics::default());
+
+        ElevatorDriver {
+            homing_timeout: config.homing_timeout_milliseconds,
+            input: InputPoller {
+                elevator: elevator.clone(),
+                poll_interval: Duration::from_millis(
+                    config.driver_channel_poll_timeout_milliseconds,
+                ),
+                current_floor: u8::MAX, // because unknown starting position
+                is_at_floor: false,
+                is_halted: false,
+                is_obstructed: false,
+                buttons: ButtonDebouncer::new(
+                    config.num_floors,
+                    Duration::from_millis(config.button_debounce_milliseconds),
+                ),
+                outputs: outputs.clone(),
+                metrics: metrics.clone(),
+                hw_event_tx,
+                tunables_rx,
+                terminate_rx,
+            },
+            output: OutputWorker {
+                elevator,
+                outputs,
+                metrics: metrics.clone(),
+                hw_command_rx,
+            },
+            metrics,
+        }
+    }
+
+    // Counters of the running driver, e.g. to watch the poll latency
+    pub fn metrics(&self) -> Arc<DriverMetrics> {
+        self.metrics.clone()
     }
 
     pub fn run(mut self) {
         info!("Starting hardware driver");
 
-        self.is_obstructed = self.elevator.obstruction();
+        loop {
+            match self.start() {
+                Ok(true) => break,
+                Ok(false) => return,
+                Err(error) => {
+                    if !self.input.recover(error) {
+                        return;
+                    }
+                }
+            }
+        }
+
+        // the worker stops once the poller drops its end of the stop channel
+        let (stop_tx, stop_rx) = channel::bounded::<()>(0);
+        let output = self.output;
+        let output_thread = thread::Builder::new()
+            .name("driver-output".into())
+            .spawn(move || output.run(stop_rx))
+            .expect("Failed to spawn driver output thread");
 
-        // reset light on init
-        for floor in 0..self.elevator.num_floors {
-            self.elevator.call_button_light(floor, HALL_UP, false);
-            self.elevator.call_button_light(floor, HALL_DOWN, false);
-            self.elevator.call_button_light(floor, CAB, false);
+        self.input.run();
+        drop(stop_tx);
+        if output_thread.join().is_err() {
+            error!("Driver output thread panicked");
         }
+        info!("Hardware driver stopped: {}", self.metrics.snapshot());
+    }
 
-        loop {
-            if self.elevator.stop_button() != self.is_halted {
-                self.is_halted = !self.is_halted;
-                let _ = self.hw_emergency_halt_tx.send(self.is_halted);
-            }
+    // Reset the outputs and home, returns false if terminated before a floor was reached
+    fn start(&mut self) -> io::Result<bool> {
+        // reset light on init
+        lock(&self.input.outputs).restore(&self.input.elevator)?;
+        self.input.is_obstructed = self.input.elevator.obstruction()?;
+        self.home()
+    }
 
-            if self.elevator.obstruction() != self.is_obstructed {
-                self.is_obstructed = !self.is_obstructed;
-                let _ = self.hw_obstruction_tx.send(self.is_obstructed);
-            }
+    // Drive to a known floor before publishing anything, the start position is unknown
+    // Returns false if terminated before a floor was reached
+    fn home(&mut self) -> io::Result<bool> {
+        let elevator = &self.input.elevator;
+        let mut homing = Homing::new(Duration::from_millis(self.homing_timeout), Instant::now());
+        let mut is_driving = false;
 
-            // ref. The Rust Programming Language - Concise Control Flow
-            if let Some(floor) = self.elevator.floor_sensor() {
-                self.current_floor = floor;
-                let _ = self.hw_floor_sensor_tx.send(floor);
+        loop {
+            match homing.update(elevator.floor_sensor()?, Instant::now()) {
+                HomingStep::Arrived(floor) => {
+                    if is_driving {
+                        elevator.motor_direction(DIRN_STOP)?;
+                    }
+                    info!("Homed to floor {}", floor);
+                    return Ok(true);
+                }
+                HomingStep::Drive(direction) => {
+                    warn!("No floor reached within homing timeout, reversing direction");
+                    elevator.motor_direction(direction)?;
+                }
+                HomingStep::Continue if !is_driving => {
+                    info!("Starting between floors, homing");
+                    elevator.motor_direction(homing.direction())?;
+                    is_driving = true;
+                }
+                HomingStep::Continue => {}
             }
 
-            for floor in 0..self.elevator.num_floors {
-                let new_cabin_order: bool = !self.requests[floor as usize][CAB as usize]
-                    && self.elevator.call_button(floor, CAB);
-                if new_cabin_order {
-                    self.requests[floor as usize][CAB as usize] = true;
-                    let _ = self.hw_request_tx.send((floor, CAB));
+            match self
+                .input
+                .terminate_rx
+                .recv_timeout(self.input.poll_interval)
+            {
+                Err(channel::RecvTimeoutError::Timeout) => {}
+                _ => {
+                    // terminating anyway, nothing to do if the stop does not get through
+                    let _ = elevator.motor_direction(DIRN_STOP);
+                    return Ok(false);
                 }
+            }
+        }
+    }
+}
 
-                let new_call_upward: bool = !self.requests[floor as usize][HALL_UP as usize]
-                    && self.elevator.call_button(floor, HALL_UP);
-                if new_call_upward {
-                    self.requests[floor as usize][HALL_UP as usize] = true;
-                    let _ = self.hw_request_tx.send((floor, HALL_UP));
-                }
+impl<E: ElevatorIo> InputPoller<E> {
+    // Poll on a fixed interval measured from the start of each pass, so the time spent
+    // sampling does not stretch the interval
+    fn run(&mut self) {
+        let mut last_report = Instant::now();
 
-                let new_call_downward: bool = !self.requests[floor as usize][HALL_DOWN as usize]
-                    && self.elevator.call_button(floor, HALL_DOWN);
-                if new_call_downward {
-                    self.requests[floor as usize][HALL_DOWN as usize] = true;
-                    let _ = self.hw_request_tx.send((floor, HALL_DOWN));
+        loop {
+            let started = Instant::now();
+            if let Err(error) = self.poll() {
+                if !self.recover(error) {
+                    break;
                 }
+                continue;
+            }
+            self.metrics.record_poll(started.elapsed());
+
+            if last_report.elapsed() >= METRICS_LOG_INTERVAL {
+                last_report = Instant::now();
+                debug!("Driver metrics: {}", self.metrics.snapshot());
             }
 
-            // receive updates from state management
+            let next_poll = channel::at(started + self.poll_interval);
             channel::select! {
-              recv(self.hw_motor_direction_rx) -> msg => {
-                match msg {
-                  Ok(msg) => self.elevator.motor_direction(msg),
-                  Err(error) => {
-                    error!("Failed to set motor direction {}", error);
-                  }
+              recv(self.tunables_rx) -> msg => {
+                if let Ok(tunables) = msg {
+                  self.poll_interval =
+                    Duration::from_millis(tunables.driver_channel_poll_timeout_milliseconds);
+                  self
+                    .buttons
+                    .set_debounce(Duration::from_millis(tunables.button_debounce_milliseconds));
                 }
               }
+              recv(self.terminate_rx) -> _ => {
+                break;
+              }
+              recv(next_poll) -> _ => {}
+            }
+        }
+    }
 
-              recv(self.hw_button_light_rx) -> msg => {
-                match msg {
-                  Ok(msg) => {
-                    let (floor, call_type, is_lit) = msg;
-                    self.elevator.call_button_light(floor, call_type, is_lit);
-                    self.requests[floor as usize][call_type as usize] = is_lit;
-                  },
-                  Err(error) => {
-                    error!("Failed to set new order or call {}", error);
-                  }
+    fn poll(&mut self) -> io::Result<()> {
+        if self.elevator.stop_button()? != self.is_halted {
+            self.is_halted = !self.is_halted;
+            self.publish(HardwareEvent::Stop(self.is_halted));
+        }
+
+        if self.elevator.obstruction()? != self.is_obstructed {
+            self.is_obstructed = !self.is_obstructed;
+            self.publish(HardwareEvent::Obstruction(self.is_obstructed));
+        }
+
+        // only publish arrivals, the sensor keeps reporting a floor while stopped at it
+        match self.elevator.floor_sensor()? {
+            Some(floor) => {
+                if !self.is_at_floor || floor != self.current_floor {
+                    self.is_at_floor = true;
+                    self.current_floor = floor;
+                    self.publish(HardwareEvent::FloorReached(floor));
                 }
-              }
+            }
+            None => self.is_at_floor = false,
+        }
 
-              recv(self.hw_floor_indicator_rx) -> msg => {
-                match msg {
-                  Ok(msg) => self.elevator.floor_indicator(msg),
-                  Err(error) => {
-                    error!("Failed to set floor indicator {}", error);
-                  }
+        self.poll_buttons()
+    }
+
+    // Report the edges of the debounced buttons
+    fn poll_buttons(&mut self) -> io::Result<()> {
+        let now = Instant::now();
+        for floor in 0..self.elevator.num_floors() {
+            for kind in ButtonKind::ALL {
+                let is_pressed = self.elevator.call_button(floor, kind.call_type())?;
+                let event = match self.buttons.update(floor, kind, is_pressed, now) {
+                    Some(true) if lock(&self.outputs).is_lit(floor, kind) => {
+                        HardwareEvent::ButtonRepressed { floor, kind }
+                    }
+                    Some(true) => HardwareEvent::ButtonPressed { floor, kind },
+                    Some(false) => HardwareEvent::ButtonReleased { floor, kind },
+                    None => continue,
+                };
+                self.publish(event);
+            }
+        }
+        Ok(())
+    }
+
+    // Wait for a lost elevator server to come back, returns false if terminated first
+    fn recover(&mut self, error: io::Error) -> bool {
+        warn!("Lost connection to the elevator: {}", error);
+        self.publish(HardwareEvent::HardwareUnavailable);
+
+        let mut backoff = RECONNECT_BACKOFF_MIN;
+        loop {
+            match self.terminate_rx.recv_timeout(backoff) {
+                Err(channel::RecvTimeoutError::Timeout) => {}
+                _ => return false,
+            }
+
+            // the outputs are only held for the restore, the worker must not wait on a connect
+            // a command it applies before the restore is cached, so it is written again
+            match self
+                .elevator
+                .reconnect()
+                .and_then(|()| lock(&self.outputs).restore(&self.elevator))
+            {
+                Ok(()) => break,
+                Err(error) => {
+                    debug!("Reconnecting failed, retrying in {:?}: {}", backoff, error);
+                    backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
                 }
-              }
+            }
+        }
 
-              recv(self.hw_door_light_rx) -> msg => {
+        info!("Reconnected to the elevator");
+        self.metrics.record_reconnect();
+        // publish the floor again, the car may have moved while the connection was down
+        self.is_at_floor = false;
+        self.publish(HardwareEvent::HardwareRestored);
+        true
+    }
+
+    // Send an input change, waiting for room if the state machine is falling behind
+    fn publish(&self, event: HardwareEvent) {
+        self.metrics.record_event();
+        match self.hw_event_tx.try_send(event) {
+            Ok(()) | Err(channel::TrySendError::Disconnected(_)) => {}
+            Err(channel::TrySendError::Full(event)) => {
+                let started = Instant::now();
+                let _ = self.hw_event_tx.send(event);
+                self.metrics.record_backpressure(started.elapsed());
+            }
+        }
+    }
+}
+
+impl<E: ElevatorIo> OutputWorker<E> {
+    // Apply the outputs until the poller stops
+    fn run(mut self, stop_rx: channel::Receiver<()>) {
+        loop {
+            channel::select! {
+              recv(self.hw_command_rx) -> msg => {
                 match msg {
-                  Ok(msg) => self.elevator.door_light(msg),
+                  Ok(command) => {
+                    self.apply(command);
+                    self.metrics.record_output();
+                  }
                   Err(error) => {
-                    error!("Failed to set eleavtor doorlight {}", error);
+                    // keep running until told to stop, like the poller
+                    error!("Lost connection to hardware command channel {}", error);
+                    self.hw_command_rx = channel::never();
                   }
                 }
               }
-              recv(self.terminate_rx) -> _ => {
+              recv(stop_rx) -> _ => {
                 break;
               }
-              default(Duration::from_millis(self.thread_sleep_time)) => {}
             }
         }
     }
+
+    fn apply(&self, command: HardwareCommand) {
+        // the poller notices the lost connection and restores the output once it is back
+        if let Err(error) = lock(&self.outputs).apply(&self.elevator, command) {
+            debug!("Deferred {:?} until reconnected: {}", command, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use driver_rust::elevio::elev::{CAB, HALL_UP};
+    use std::collections::HashSet;
+
+    #[derive(Default)]
+    struct FakeState {
+        pressed: HashSet<(u8, u8)>,
+        lit: HashSet<(u8, u8)>,
+        motor_direction: u8,
+        is_door_lit: bool,
+        is_down: bool, // every call fails, like a lost elevator server
+    }
+
+    // Elevator resting at the bottom floor, with buttons the test can press
+    #[derive(Clone, Default)]
+    struct FakeIo {
+        state: Arc<Mutex<FakeState>>,
+    }
+
+    impl FakeIo {
+        fn state(&self) -> io::Result<MutexGuard<'_, FakeState>> {
+            let state = self.state.lock().unwrap();
+            match state.is_down {
+                true => Err(io::ErrorKind::ConnectionReset.into()),
+                false => Ok(state),
+            }
+        }
+    }
+
+    impl ElevatorIo for FakeIo {
+        fn num_floors(&self) -> u8 {
+            4
+        }
+        fn motor_direction(&self, direction: u8) -> io::Result<()> {
+            self.state()?.motor_direction = direction;
+            Ok(())
+        }
+        fn call_button(&self, floor: u8, call_type: u8) -> io::Result<bool> {
+            Ok(self.state()?.pressed.contains(&(floor, call_type)))
+        }
+        fn call_button_light(&self, floor: u8, call_type: u8, is_lit: bool) -> io::Result<()> {
+            let lit = &mut self.state()?.lit;
+            if is_lit {
+                lit.insert((floor, call_type));
+            } else {
+                lit.remove(&(floor, call_type));
+            }
+            Ok(())
+        }
+        fn floor_sensor(&self) -> io::Result<Option<u8>> {
+            self.state().map(|_| Some(0))
+        }
+        fn stop_button(&self) -> io::Result<bool> {
+            self.state().map(|_| false)
+        }
+        fn stop_button_light(&self, _: bool) -> io::Result<()> {
+            self.state().map(|_| ())
+        }
+        fn obstruction(&self) -> io::Result<bool> {
+            self.state().map(|_| false)
+        }
+        fn door_light(&self, is_lit: bool) -> io::Result<()> {
+            self.state()?.is_door_lit = is_lit;
+            Ok(())
+        }
+        fn floor_indicator(&self, _: u8) -> io::Result<()> {
+            self.state().map(|_| ())
+        }
+        fn reconnect(&self) -> io::Result<()> {
+            self.state().map(|_| ())
+        }
+    }
+
+    struct TestDriver {
+        io: FakeIo,
+        metrics: Arc<DriverMetrics>,
+        event_rx: channel::Receiver<HardwareEvent>,
+        command_tx: channel::Sender<HardwareCommand>,
+        terminate_tx: channel::Sender<()>,
+        handle: thread::JoinHandle<()>,
+    }
+
+    fn start_driver(event_capacity: Option<usize>) -> TestDriver {
+        let io = FakeIo::default();
+        let (event_tx, event_rx) = match event_capacity {
+            Some(capacity) => channel::bounded(capacity),
+            None => channel::unbounded(),
+        };
+        let (command_tx, command_rx) = channel::unbounded();
+        let (_, tunables_rx) = channel::unbounded();
+        let (terminate_tx, terminate_rx) = channel::unbounded();
+
+        let driver = ElevatorDriver::with_io(
+            io.clone(),
+            &HardwareConfig::default(),
+            event_tx,
+            command_rx,
+            tunables_rx,
+            terminate_rx,
+        );
+        let metrics = driver.metrics();
+        TestDriver {
+            io,
+            metrics,
+            event_rx,
+            command_tx,
+            terminate_tx,
+            handle: thread::spawn(move || driver.run()),
+        }
+    }
+
+    fn stop_driver(driver: TestDriver) {
+        driver.terminate_tx.send(()).unwrap();
+        driver.handle.join().unwrap();
+    }
+
+    fn pressed(floor: u8, kind: ButtonKind) -> HardwareEvent {
+        HardwareEvent::ButtonPressed { floor, kind }
+    }
+
+    #[test]
+    fn test_driver_runs_against_any_io() {
+        let driver = start_driver(None);
+        let timeout = Duration::from_secs(1);
+        assert_eq!(
+            driver.event_rx.recv_timeout(timeout),
+            Ok(HardwareEvent::FloorReached(0))
+        );
+
+        // a held button is only reported once
+        driver.io.state.lock().unwrap().pressed.insert((2, CAB));
+        assert_eq!(
+            driver.event_rx.recv_timeout(timeout),
+            Ok(pressed(2, ButtonKind::Cab))
+        );
+        assert!(driver
+            .event_rx
+            .recv_timeout(Duration::from_millis(50))
+            .is_err());
+
+        for command in [
+            HardwareCommand::SetButtonLight {
+                floor: 2,
+                kind: ButtonKind::Cab,
+                is_lit: true,
+            },
+            HardwareCommand::SetMotor(Some(Direction::Down)),
+        ] {
+            driver.command_tx.send(command).unwrap();
+        }
+        thread::sleep(Duration::from_millis(50));
+        {
+            let state = driver.io.state.lock().unwrap();
+            assert!(state.lit.contains(&(2, CAB)));
+            assert_eq!(state.motor_direction, DIRN_DOWN);
+        }
+
+        // pressing the lit button again is a re-press
+        driver.io.state.lock().unwrap().pressed.remove(&(2, CAB));
+        assert_eq!(
+            driver.event_rx.recv_timeout(timeout),
+            Ok(HardwareEvent::ButtonReleased {
+                floor: 2,
+                kind: ButtonKind::Cab
+            })
+        );
+        driver.io.state.lock().unwrap().pressed.insert((2, CAB));
+        assert_eq!(
+            driver.event_rx.recv_timeout(timeout),
+            Ok(HardwareEvent::ButtonRepressed {
+                floor: 2,
+                kind: ButtonKind::Cab
+            })
+        );
+
+        stop_driver(driver);
+    }
+
+    #[test]
+    fn test_outputs_are_applied_while_inputs_back_up() {
+        let driver = start_driver(Some(1));
+        let timeout = Duration::from_secs(1);
+        assert_eq!(
+            driver.event_rx.recv_timeout(timeout),
+            Ok(HardwareEvent::FloorReached(0))
+        );
+        assert!(driver.metrics.snapshot().polls > 0);
+
+        // nobody reads the events, so the poller ends up waiting on the full channel
+        driver
+            .io
+            .state
+            .lock()
+            .unwrap()
+            .pressed
+            .extend((0..3).map(|floor| (floor, CAB)));
+        thread::sleep(Duration::from_millis(100));
+
+        driver
+            .command_tx
+            .send(HardwareCommand::SetDoorLight(true))
+            .unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(driver.io.state.lock().unwrap().is_door_lit);
+
+        // a scan can start between the presses, so the order is not fixed
+        let events: Vec<HardwareEvent> = (0..3)
+            .map(|_| driver.event_rx.recv_timeout(timeout).unwrap())
+            .collect();
+        for floor in 0..3 {
+            assert!(events.contains(&pressed(floor, ButtonKind::Cab)));
+        }
+
+        let snapshot = driver.metrics.snapshot();
+        assert!(snapshot.backpressure_events >= 1);
+        assert!(snapshot.backpressure_wait > Duration::ZERO);
+        assert!(snapshot.outputs_applied >= 1);
+
+        stop_driver(driver);
+    }
+
+    #[test]
+    fn test_reconnect_restores_outputs() {
+        let driver = start_driver(None);
+        let timeout = Duration::from_secs(1);
+        assert_eq!(
+            driver.event_rx.recv_timeout(timeout),
+            Ok(HardwareEvent::FloorReached(0))
+        );
+        for command in [
+            HardwareCommand::SetButtonLight {
+                floor: 1,
+                kind: ButtonKind::HallUp,
+                is_lit: true,
+            },
+            HardwareCommand::SetMotor(Some(Direction::Up)),
+        ] {
+            driver.command_tx.send(command).unwrap();
+        }
+        thread::sleep(Duration::from_millis(50));
+
+        // the server restarts with everything off
+        *driver.io.state.lock().unwrap() = FakeState {
+            is_down: true,
+            ..FakeState::default()
+        };
+        assert_eq!(
+            driver.event_rx.recv_timeout(timeout),
+            Ok(HardwareEvent::HardwareUnavailable)
+        );
+        // requested while down, applied on reconnect
+        driver
+            .command_tx
+            .send(HardwareCommand::SetDoorLight(true))
+            .unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        driver.io.state.lock().unwrap().is_down = false;
+        assert_eq!(
+            driver.event_rx.recv_timeout(timeout),
+            Ok(HardwareEvent::HardwareRestored)
+        );
+        assert_eq!(
+            driver.event_rx.recv_timeout(timeout),
+            Ok(HardwareEvent::FloorReached(0))
+        );
+        {
+            let state = driver.io.state.lock().unwrap();
+            assert!(state.lit.contains(&(1, HALL_UP)));
+            assert_eq!(state.motor_direction, DIRN_UP);
+            assert!(state.is_door_lit);
+        }
+        assert_eq!(driver.metrics.snapshot().reconnects, 1);
+
+        stop_driver(driver);
+    }
 }
@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use driver_rust::elevio::elev::{DIRN_DOWN, DIRN_STOP, DIRN_UP};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomingStep {
+    // Keep driving in the current direction
+    Continue,
+    // Start driving in the given motor direction
+    Drive(u8),
+    // A floor has been reached and the motor should be stopped
+    Arrived(u8),
+}
+
+// Routine for finding a known floor when starting between floors
+// The car is driven downward first, if no floor is reached within the timeout the direction
+// is reversed, and the two directions alternate until the floor sensor reports a floor.
+#[derive(Debug)]
+pub struct Homing {
+    timeout: Duration,
+    direction: u8,
+    deadline: Instant,
+}
+
+impl Homing {
+    pub fn new(timeout: Duration, now: Instant) -> Self {
+        Self {
+            timeout,
+            direction: DIRN_DOWN,
+            deadline: now + timeout,
+        }
+    }
+
+    // Motor direction the routine is currently driving in
+    pub fn direction(&self) -> u8 {
+        self.direction
+    }
+
+    // Advance the routine with the latest floor sensor reading
+    pub fn update(&mut self, floor: Option<u8>, now: Instant) -> HomingStep {
+        if let Some(floor) = floor {
+            self.direction = DIRN_STOP;
+            return HomingStep::Arrived(floor);
+        }
+
+        if now < self.deadline {
+            return HomingStep::Continue;
+        }
+
+        self.direction = if self.direction == DIRN_DOWN {
+            DIRN_UP
+        } else {
+            DIRN_DOWN
+        };
+        self.deadline = now + self.timeout;
+        HomingStep::Drive(self.direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_homing_starts_downward() {
+        let start = Instant::now();
+        let mut homing = Homing::new(TIMEOUT, start);
+
+        assert_eq!(homing.direction(), DIRN_DOWN);
+        assert_eq!(
+            homing.update(None, start + Duration::from_secs(1)),
+            HomingStep::Continue
+        );
+        assert_eq!(
+            homing.update(Some(0), start + Duration::from_secs(2)),
+            HomingStep::Arrived(0)
+        );
+        assert_eq!(homing.direction(), DIRN_STOP);
+    }
+
+    #[test]
+    fn test_homing_falls_back_to_upward() {
+        let start = Instant::now();
+        let mut homing = Homing::new(TIMEOUT, start);
+
+        assert_eq!(
+            homing.update(None, start + TIMEOUT),
+            HomingStep::Drive(DIRN_UP)
+        );
+        assert_eq!(
+            homing.update(None, start + TIMEOUT + Duration::from_secs(1)),
+            HomingStep::Continue
+        );
+
+        // both directions failed, so keep alternating until a floor shows up
+        assert_eq!(
+            homing.update(None, start + TIMEOUT * 2),
+            HomingStep::Drive(DIRN_DOWN)
+        );
+        assert_eq!(
+            homing.update(Some(2), start + TIMEOUT * 2 + Duration::from_secs(1)),
+            HomingStep::Arrived(2)
+        );
+    }
+}
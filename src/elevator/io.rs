@@ -0,0 +1,26 @@
+use std::io;
+
+// Every elevio message, request or reply, is exactly this long
+pub const MESSAGE_BYTES: usize = 4;
+
+// Inputs and outputs of a single elevator
+// The values follow the elevio protocol used by driver_rust, call types are HALL_UP, HALL_DOWN
+// and CAB, and motor directions are DIRN_DOWN, DIRN_STOP and DIRN_UP. Every call can fail when
+// the elevator is reached over a connection that was lost.
+pub trait ElevatorIo {
+    fn num_floors(&self) -> u8;
+    fn motor_direction(&self, direction: u8) -> io::Result<()>;
+    fn call_button(&self, floor: u8, call_type: u8) -> io::Result<bool>;
+    fn call_button_light(&self, floor: u8, call_type: u8, is_lit: bool) -> io::Result<()>;
+    fn floor_sensor(&self) -> io::Result<Option<u8>>;
+    fn stop_button(&self) -> io::Result<bool>;
+    fn stop_button_light(&self, is_lit: bool) -> io::Result<()>;
+    fn obstruction(&self) -> io::Result<bool>;
+    fn door_light(&self, is_lit: bool) -> io::Result<()>;
+    fn floor_indicator(&self, floor: u8) -> io::Result<()>;
+
+    // Connect again after a failed call, IO without a connection has nothing to restore
+    fn reconnect(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Counters shared by the driver threads, cheap enough to update on every poll
+#[derive(Debug, Default)]
+pub struct DriverMetrics {
+    polls: AtomicU64,
+    poll_latency_total_micros: AtomicU64,
+    poll_latency_max_micros: AtomicU64,
+    events_sent: AtomicU64,
+    backpressure_events: AtomicU64, // sends that found the channel full
+    backpressure_wait_micros: AtomicU64,
+    outputs_applied: AtomicU64,
+    reconnects: AtomicU64,
+}
+
+impl DriverMetrics {
+    // One pass over every input took the given time
+    pub fn record_poll(&self, latency: Duration) {
+        let micros = latency.as_micros() as u64;
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.poll_latency_total_micros
+            .fetch_add(micros, Ordering::Relaxed);
+        self.poll_latency_max_micros
+            .fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub fn record_event(&self) {
+        self.events_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // An event waited the given time for room in a full channel
+    pub fn record_backpressure(&self, waited: Duration) {
+        self.backpressure_events.fetch_add(1, Ordering::Relaxed);
+        self.backpressure_wait_micros
+            .fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_output(&self) {
+        self.outputs_applied.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let polls = self.polls.load(Ordering::Relaxed);
+        let total = self.poll_latency_total_micros.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            polls,
+            average_poll_latency: Duration::from_micros(total.checked_div(polls).unwrap_or(0)),
+            max_poll_latency: Duration::from_micros(
+                self.poll_latency_max_micros.load(Ordering::Relaxed),
+            ),
+            events_sent: self.events_sent.load(Ordering::Relaxed),
+            backpressure_events: self.backpressure_events.load(Ordering::Relaxed),
+            backpressure_wait: Duration::from_micros(
+                self.backpressure_wait_micros.load(Ordering::Relaxed),
+            ),
+            outputs_applied: self.outputs_applied.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub polls: u64,
+    pub average_poll_latency: Duration,
+    pub max_poll_latency: Duration,
+    pub events_sent: u64,
+    pub backpressure_events: u64,
+    pub backpressure_wait: Duration,
+    pub outputs_applied: u64,
+    pub reconnects: u64,
+}
+
+impl fmt::Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} polls, poll latency {:?} average {:?} max, {} events sent, {} full channel waits ({:?}), {} outputs applied, {} reconnects",
+            self.polls,
+            self.average_poll_latency,
+            self.max_poll_latency,
+            self.events_sent,
+            self.backpressure_events,
+            self.backpressure_wait,
+            self.outputs_applied,
+            self.reconnects
+        )
+    }
+}
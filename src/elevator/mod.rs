@@ -1,3 +1,19 @@
+pub mod button;
+pub mod door;
+pub mod elevio_client;
+pub mod elevio_server;
+pub mod events;
+pub mod fsm;
 pub mod hardware;
+pub mod homing;
+pub mod io;
+pub mod metrics;
+pub mod simulator;
 
-pub use hardware::ElevatorDriver;
\ No newline at end of file
+pub use elevio_client::ElevioClient;
+pub use events::{ButtonKind, HardwareCommand, HardwareEvent};
+pub use fsm::{ElevatorFsm, ElevatorState};
+pub use hardware::ElevatorDriver;
+pub use io::ElevatorIo;
+pub use metrics::{DriverMetrics, MetricsSnapshot};
+pub use simulator::{SimulatedElevator, SimulatorConfig, SimulatorEvent};
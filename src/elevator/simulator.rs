@@ -0,0 +1,566 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use driver_rust::elevio::elev::{CAB, DIRN_DOWN, DIRN_STOP, DIRN_UP, HALL_DOWN, HALL_UP};
+
+use super::io::ElevatorIo;
+
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    pub num_floors: u8,
+    pub travel_time: Duration, // time to travel between two adjacent floors
+    pub sensor_width: f64,     // fraction of the distance between floors covered by a sensor
+    pub start_position: f64,   // in floors, a fraction starts the car between floors
+    pub time_scale: f64,       // virtual seconds per real second, 0 to only advance manually
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            num_floors: 4,
+            travel_time: Duration::from_millis(2000),
+            sensor_width: 0.2,
+            start_position: 0.0,
+            time_scale: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulatorEvent {
+    Press(u8, u8), // floor and call type
+    Release(u8, u8),
+    SetObstruction(bool),
+    SetStopButton(bool),
+}
+
+#[derive(Debug)]
+struct SimulatorState {
+    config: SimulatorConfig,
+    virtual_time: Duration,
+    last_sync: Instant,
+    position: f64,
+    motor_direction: u8,
+    pressed: HashSet<(u8, u8)>,
+    lights: HashSet<(u8, u8)>,
+    is_obstructed: bool,
+    is_stop_pressed: bool,
+    is_stop_lit: bool,
+    is_door_lit: bool,
+    floor_indicator: Option<u8>,
+    script: Vec<(Duration, SimulatorEvent)>, // pending events, ordered by virtual time
+}
+
+// Elevator simulated in-process, a stand-in for the elevator server
+// The car moves with constant speed while the motor runs and stops at the top and bottom
+// floors. Time is virtual: it follows the wall clock scaled by the time scale, and can also be
+// advanced by hand, so tests can run a whole trip faster than real time. Clones share the same
+// elevator, so a test can keep one to press buttons while the driver owns another.
+#[derive(Debug, Clone)]
+pub struct SimulatedElevator {
+    state: Arc<Mutex<SimulatorState>>,
+}
+
+impl SimulatedElevator {
+    pub fn new(config: SimulatorConfig) -> Self {
+        let position = config
+            .start_position
+            .clamp(0.0, config.num_floors.saturating_sub(1) as f64);
+        let state = SimulatorState {
+            config,
+            virtual_time: Duration::ZERO,
+            last_sync: Instant::now(),
+            position,
+            motor_direction: DIRN_STOP,
+            pressed: HashSet::new(),
+            lights: HashSet::new(),
+            is_obstructed: false,
+            is_stop_pressed: false,
+            is_stop_lit: false,
+            is_door_lit: false,
+            floor_indicator: None,
+            script: Vec::new(),
+        };
+
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    // Run an event once the given virtual time has passed from now
+    pub fn schedule(&self, delay: Duration, event: SimulatorEvent) {
+        let mut state = self.synced();
+        let at = state.virtual_time + delay;
+        let index = state.script.partition_point(|(time, _)| *time <= at);
+        state.script.insert(index, (at, event));
+        state.run_due_events();
+    }
+
+    pub fn apply(&self, event: SimulatorEvent) {
+        self.schedule(Duration::ZERO, event);
+    }
+
+    pub fn press(&self, floor: u8, call_type: u8) {
+        self.apply(SimulatorEvent::Press(floor, call_type));
+    }
+
+    pub fn release(&self, floor: u8, call_type: u8) {
+        self.apply(SimulatorEvent::Release(floor, call_type));
+    }
+
+    pub fn set_obstruction(&self, is_obstructed: bool) {
+        self.apply(SimulatorEvent::SetObstruction(is_obstructed));
+    }
+
+    pub fn set_stop_button(&self, is_pressed: bool) {
+        self.apply(SimulatorEvent::SetStopButton(is_pressed));
+    }
+
+    // Move virtual time forward by hand, on top of any scaled wall clock time
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.synced();
+        state.advance(duration);
+    }
+
+    pub fn virtual_time(&self) -> Duration {
+        self.synced().virtual_time
+    }
+
+    // Position of the car in floors, e.g. 1.5 is halfway between the second and third floor
+    pub fn position(&self) -> f64 {
+        self.synced().position
+    }
+
+    pub fn is_between_floors(&self) -> bool {
+        self.synced().sensed_floor().is_none()
+    }
+
+    pub fn current_motor_direction(&self) -> u8 {
+        self.synced().motor_direction
+    }
+
+    pub fn is_button_lit(&self, floor: u8, call_type: u8) -> bool {
+        self.synced().lights.contains(&(floor, call_type))
+    }
+
+    pub fn is_door_lit(&self) -> bool {
+        self.synced().is_door_lit
+    }
+
+    pub fn is_stop_lit(&self) -> bool {
+        self.synced().is_stop_lit
+    }
+
+    pub fn current_floor_indicator(&self) -> Option<u8> {
+        self.synced().floor_indicator
+    }
+
+    // Lock the state after bringing it up to date with the wall clock
+    fn synced(&self) -> MutexGuard<'_, SimulatorState> {
+        let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_sync);
+        state.last_sync = now;
+        let scaled = elapsed.mul_f64(state.config.time_scale.max(0.0));
+        state.advance(scaled);
+        state
+    }
+}
+
+impl SimulatorState {
+    // Advance virtual time, moving the car and running scripted events in order
+    fn advance(&mut self, duration: Duration) {
+        let end = self.virtual_time + duration;
+        while let Some(&(at, _)) = self.script.first().filter(|(at, _)| *at <= end) {
+            self.move_car(at - self.virtual_time);
+            self.virtual_time = at;
+            self.run_due_events();
+        }
+        self.move_car(end - self.virtual_time);
+        self.virtual_time = end;
+    }
+
+    fn move_car(&mut self, duration: Duration) {
+        let distance =
+            duration.as_secs_f64() / self.config.travel_time.as_secs_f64().max(f64::EPSILON);
+        let top = self.config.num_floors.saturating_sub(1) as f64;
+        self.position = match self.motor_direction {
+            DIRN_UP => (self.position + distance).min(top),
+            DIRN_DOWN => (self.position - distance).max(0.0),
+            _ => self.position,
+        };
+    }
+
+    fn run_due_events(&mut self) {
+        let due = self
+            .script
+            .partition_point(|(at, _)| *at <= self.virtual_time);
+        let events: Vec<SimulatorEvent> =
+            self.script.drain(..due).map(|(_, event)| event).collect();
+        for event in events {
+            match event {
+                SimulatorEvent::Press(floor, call_type) => {
+                    self.pressed.insert((floor, call_type));
+                }
+                SimulatorEvent::Release(floor, call_type) => {
+                    self.pressed.remove(&(floor, call_type));
+                }
+                SimulatorEvent::SetObstruction(is_obstructed) => self.is_obstructed = is_obstructed,
+                SimulatorEvent::SetStopButton(is_pressed) => self.is_stop_pressed = is_pressed,
+            }
+        }
+    }
+
+    fn sensed_floor(&self) -> Option<u8> {
+        let nearest = self.position.round();
+        ((self.position - nearest).abs() <= self.config.sensor_width / 2.0).then_some(nearest as u8)
+    }
+}
+
+impl fmt::Display for SimulatedElevator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.synced();
+        let motor = match state.motor_direction {
+            DIRN_UP => "up",
+            DIRN_DOWN => "down",
+            _ => "stopped",
+        };
+        let on_off = |is_on: bool| if is_on { "on" } else { "off" };
+
+        let mut lights: Vec<&(u8, u8)> = state.lights.iter().collect();
+        lights.sort();
+        let lights: Vec<String> = lights
+            .into_iter()
+            .map(|(floor, call_type)| match *call_type {
+                HALL_UP => format!("up {}", floor),
+                HALL_DOWN => format!("down {}", floor),
+                CAB => format!("cab {}", floor),
+                _ => format!("{} {}", call_type, floor),
+            })
+            .collect();
+
+        write!(
+            f,
+            "t={:.1}s position {:.2} motor {} door {} obstruction {} stop {} lights [{}]",
+            state.virtual_time.as_secs_f64(),
+            state.position,
+            motor,
+            on_off(state.is_door_lit),
+            on_off(state.is_obstructed),
+            on_off(state.is_stop_pressed),
+            lights.join(", ")
+        )
+    }
+}
+
+impl ElevatorIo for SimulatedElevator {
+    fn num_floors(&self) -> u8 {
+        self.synced().config.num_floors
+    }
+
+    fn motor_direction(&self, direction: u8) -> io::Result<()> {
+        self.synced().motor_direction = direction;
+        Ok(())
+    }
+
+    fn call_button(&self, floor: u8, call_type: u8) -> io::Result<bool> {
+        Ok(self.synced().pressed.contains(&(floor, call_type)))
+    }
+
+    fn call_button_light(&self, floor: u8, call_type: u8, is_lit: bool) -> io::Result<()> {
+        let mut state = self.synced();
+        if is_lit {
+            state.lights.insert((floor, call_type));
+        } else {
+            state.lights.remove(&(floor, call_type));
+        }
+        Ok(())
+    }
+
+    fn floor_sensor(&self) -> io::Result<Option<u8>> {
+        Ok(self.synced().sensed_floor())
+    }
+
+    fn stop_button(&self) -> io::Result<bool> {
+        Ok(self.synced().is_stop_pressed)
+    }
+
+    fn stop_button_light(&self, is_lit: bool) -> io::Result<()> {
+        self.synced().is_stop_lit = is_lit;
+        Ok(())
+    }
+
+    fn obstruction(&self) -> io::Result<bool> {
+        Ok(self.synced().is_obstructed)
+    }
+
+    fn door_light(&self, is_lit: bool) -> io::Result<()> {
+        self.synced().is_door_lit = is_lit;
+        Ok(())
+    }
+
+    fn floor_indicator(&self, floor: u8) -> io::Result<()> {
+        self.synced().floor_indicator = Some(floor);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    use crossbeam_channel as channel;
+    use uuid::Uuid;
+
+    use crate::clock::init_clock_with_random_id;
+    use crate::config::{HaltPolicy, HardwareConfig, Tunables};
+    use crate::elevator::{ElevatorDriver, ElevatorFsm};
+    use crate::queue::scheduler::{FifoScheduler, PeerState};
+    use crate::queue::{Call, Command, OrderQueue};
+
+    // Real time the full loop tests wait for the elevator before giving up
+    const LOOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn manual_elevator(start_position: f64) -> SimulatedElevator {
+        SimulatedElevator::new(SimulatorConfig {
+            travel_time: Duration::from_secs(2),
+            start_position,
+            time_scale: 0.0,
+            ..SimulatorConfig::default()
+        })
+    }
+
+    // Driver and state machine running against a simulator in accelerated time
+    struct LoopHarness {
+        elevator: SimulatedElevator,
+        // held so the state machine does not see these channels disconnect
+        _hall_call_tx: channel::Sender<Call>,
+        _hall_call_released_tx: channel::Sender<Call>,
+        _restored_commands_tx: channel::Sender<Vec<Command>>,
+        _peer_states_tx: channel::Sender<Vec<PeerState>>,
+        _tunables_tx: Vec<channel::Sender<Tunables>>,
+        terminate_tx: Option<channel::Sender<()>>,
+        threads: Vec<thread::JoinHandle<()>>,
+    }
+
+    impl Drop for LoopHarness {
+        fn drop(&mut self) {
+            // the threads stop once the terminate channel disconnects
+            self.terminate_tx.take();
+            for thread in self.threads.drain(..) {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn loop_config() -> HardwareConfig {
+        HardwareConfig {
+            num_floors: 4,
+            driver_address: "localhost".to_string(),
+            driver_port: 15657,
+            driver_channel_poll_timeout_milliseconds: 5,
+            button_debounce_milliseconds: 20,
+            door_open_duration_milliseconds: 200,
+            door_stuck_timeout_milliseconds: 5000,
+            homing_timeout_milliseconds: 5000,
+            halt_policy: HaltPolicy::StopAndKeepOrders,
+            order_expiry_seconds: 60,
+        }
+    }
+
+    fn setup_loop(start_position: f64) -> LoopHarness {
+        let _ = init_clock_with_random_id();
+        let config = loop_config();
+
+        // two virtual seconds between floors pass in 200 milliseconds
+        let elevator = SimulatedElevator::new(SimulatorConfig {
+            num_floors: config.num_floors,
+            travel_time: Duration::from_secs(2),
+            start_position,
+            time_scale: 10.0,
+            ..SimulatorConfig::default()
+        });
+
+        let (event_tx, event_rx) = channel::unbounded();
+        let (command_tx, command_rx) = channel::unbounded();
+        let (hall_request_tx, _) = channel::unbounded();
+        let (hall_call_tx, hall_call_rx) = channel::unbounded();
+        let (hall_call_released_tx, hall_call_released_rx) = channel::unbounded();
+        let (hall_call_served_tx, _) = channel::unbounded();
+        let (cab_orders_tx, _) = channel::unbounded();
+        let (restored_commands_tx, restored_commands_rx) = channel::unbounded();
+        let (driver_tunables_tx, driver_tunables_rx) = channel::unbounded();
+        let (fsm_tunables_tx, fsm_tunables_rx) = channel::unbounded();
+        let (availability_tx, _) = channel::unbounded();
+        let (status_tx, _) = channel::unbounded();
+        let (peer_states_tx, peer_states_rx) = channel::unbounded();
+        let (terminate_tx, terminate_rx) = channel::unbounded();
+
+        let driver = ElevatorDriver::with_io(
+            elevator.clone(),
+            &config,
+            event_tx,
+            command_rx,
+            driver_tunables_rx,
+            terminate_rx.clone(),
+        );
+        let fsm = ElevatorFsm::new(
+            &config,
+            Uuid::new_v4(),
+            Box::new(FifoScheduler),
+            OrderQueue::new(),
+            command_tx,
+            event_rx,
+            hall_request_tx,
+            hall_call_rx,
+            hall_call_released_rx,
+            hall_call_served_tx,
+            cab_orders_tx,
+            restored_commands_rx,
+            fsm_tunables_rx,
+            availability_tx,
+            status_tx,
+            peer_states_rx,
+            terminate_rx,
+        );
+
+        LoopHarness {
+            elevator,
+            _hall_call_tx: hall_call_tx,
+            _hall_call_released_tx: hall_call_released_tx,
+            _restored_commands_tx: restored_commands_tx,
+            _peer_states_tx: peer_states_tx,
+            _tunables_tx: vec![driver_tunables_tx, fsm_tunables_tx],
+            terminate_tx: Some(terminate_tx),
+            threads: vec![
+                thread::spawn(move || driver.run()),
+                thread::spawn(move || fsm.run()),
+            ],
+        }
+    }
+
+    // Poll the simulator until the condition holds, false if it never did
+    fn wait_until(
+        elevator: &SimulatedElevator,
+        condition: impl Fn(&SimulatedElevator) -> bool,
+    ) -> bool {
+        let deadline = Instant::now() + LOOP_TIMEOUT;
+        while Instant::now() < deadline {
+            if condition(elevator) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        false
+    }
+
+    #[test]
+    fn test_car_travels_at_configured_speed() {
+        let elevator = manual_elevator(0.0);
+        assert_eq!(elevator.floor_sensor().unwrap(), Some(0));
+
+        elevator.motor_direction(DIRN_UP).unwrap();
+        elevator.advance(Duration::from_secs(1));
+        assert!(elevator.is_between_floors());
+        assert_eq!(elevator.floor_sensor().unwrap(), None);
+
+        elevator.advance(Duration::from_secs(1));
+        assert_eq!(elevator.floor_sensor().unwrap(), Some(1));
+
+        // the car stops at the top floor however long the motor runs
+        elevator.advance(Duration::from_secs(60));
+        assert_eq!(elevator.floor_sensor().unwrap(), Some(3));
+        assert_eq!(elevator.position(), 3.0);
+
+        elevator.motor_direction(DIRN_DOWN).unwrap();
+        elevator.advance(Duration::from_secs(3));
+        assert!((elevator.position() - 1.5).abs() < 1e-9);
+        elevator.motor_direction(DIRN_STOP).unwrap();
+        elevator.advance(Duration::from_secs(10));
+        assert!(elevator.is_between_floors());
+    }
+
+    #[test]
+    fn test_scripted_events_follow_virtual_time() {
+        let elevator = manual_elevator(0.0);
+        elevator.schedule(Duration::from_secs(1), SimulatorEvent::Press(2, CAB));
+        elevator.schedule(Duration::from_secs(2), SimulatorEvent::SetObstruction(true));
+        elevator.schedule(Duration::from_secs(3), SimulatorEvent::Release(2, CAB));
+
+        assert!(!elevator.call_button(2, CAB).unwrap());
+        elevator.advance(Duration::from_millis(1500));
+        assert!(elevator.call_button(2, CAB).unwrap());
+        assert!(!elevator.obstruction().unwrap());
+
+        elevator.advance(Duration::from_secs(2));
+        assert!(!elevator.call_button(2, CAB).unwrap());
+        assert!(elevator.obstruction().unwrap());
+        assert_eq!(elevator.virtual_time(), Duration::from_millis(3500));
+    }
+
+    #[test]
+    fn test_cab_order_is_served_end_to_end() {
+        let harness = setup_loop(0.0);
+        let elevator = &harness.elevator;
+        assert!(wait_until(elevator, |elevator| elevator
+            .current_floor_indicator()
+            == Some(0)));
+
+        elevator.press(2, CAB);
+        assert!(wait_until(elevator, |elevator| elevator.is_button_lit(2, CAB)));
+        elevator.release(2, CAB);
+
+        assert!(wait_until(elevator, |elevator| elevator.is_door_lit()));
+        assert_eq!(elevator.floor_sensor().unwrap(), Some(2));
+        assert_eq!(elevator.current_motor_direction(), DIRN_STOP);
+        assert_eq!(elevator.current_floor_indicator(), Some(2));
+        assert!(!elevator.is_button_lit(2, CAB));
+
+        assert!(wait_until(elevator, |elevator| !elevator.is_door_lit()));
+        assert_eq!(elevator.floor_sensor().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_homes_from_between_floors() {
+        let harness = setup_loop(1.5);
+        let elevator = &harness.elevator;
+        assert!(elevator.is_between_floors());
+
+        assert!(wait_until(elevator, |elevator| elevator
+            .current_floor_indicator()
+            .is_some()));
+        assert!(!elevator.is_between_floors());
+        assert_eq!(elevator.current_motor_direction(), DIRN_STOP);
+
+        elevator.press(3, CAB);
+        assert!(wait_until(elevator, |elevator| {
+            elevator.is_door_lit() && elevator.floor_sensor().unwrap() == Some(3)
+        }));
+    }
+
+    #[test]
+    fn test_obstruction_holds_door_open() {
+        let harness = setup_loop(0.0);
+        let elevator = &harness.elevator;
+        assert!(wait_until(elevator, |elevator| elevator
+            .current_floor_indicator()
+            == Some(0)));
+
+        elevator.press(1, CAB);
+        assert!(wait_until(elevator, |elevator| elevator.is_door_lit()));
+        elevator.release(1, CAB);
+        elevator.set_obstruction(true);
+
+        // well past the door open duration the door is still held open
+        thread::sleep(Duration::from_millis(600));
+        assert!(elevator.is_door_lit());
+
+        elevator.set_obstruction(false);
+        assert!(wait_until(elevator, |elevator| !elevator.is_door_lit()));
+        assert_eq!(elevator.floor_sensor().unwrap(), Some(1));
+    }
+}
@@ -0,0 +1,8 @@
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod elevator;
+pub mod network;
+pub mod queue;
+pub mod reload;
+pub mod wire;
@@ -1,43 +1,151 @@
-mod clock;
-
-use clock::init_clock;
+use clap::Parser;
 use crossbeam_channel as channel;
-use elevator::ElevatorDriver;
+use elevators::cli::Cli;
+use elevators::clock::{get_clock_uuid, init_clock};
+use elevators::config::{self, Tunables};
+use elevators::elevator::hardware::HARDWARE_CHANNEL_CAPACITY;
+use elevators::elevator::{ElevatorDriver, ElevatorFsm, HardwareCommand, HardwareEvent};
+use elevators::network::{Coordinator, Message, NetworkNode, PeerEvent};
+use elevators::queue::assigner::ElevatorStatus;
+use elevators::queue::scheduler::{self, PeerState};
+use elevators::queue::{Call, Command, Direction, OrderQueue};
+use elevators::reload::ConfigWatcher;
+use log::info;
 use std::thread;
-use uhlc::ID;
+use std::time::Duration;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = config::load();
-    let node_id = ID::try_from([0x01, 0x02, 0x03])?;
+    let cli = Cli::parse();
+
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(log_level) = cli.log_level {
+        logger.filter_level(log_level);
+    }
+    logger.init();
+
+    let mut config = config::load(&cli.config, cli.profile.as_deref())?;
+    cli.apply(&mut config);
+    config.validate()?;
+    info!("{}", config);
+    let node_id = cli.node_id()?;
+    info!("Starting node {}", node_id);
     init_clock(node_id)?;
 
-    // hardware
+    // configuration
+    let (driver_tunables_tx, driver_tunables_rx) = channel::unbounded::<Tunables>();
+    let (fsm_tunables_tx, fsm_tunables_rx) = channel::unbounded::<Tunables>();
+    let (coordinator_tunables_tx, coordinator_tunables_rx) = channel::unbounded::<Tunables>();
+
+    // hardware, bounded so a stalled reader shows up as backpressure in the driver metrics
     let (_hw_terminate_tx, hw_terminate_rx) = channel::unbounded::<()>();
-    let (_, hw_motor_direction_rx) = channel::unbounded::<u8>();
-    let (_, hw_button_light_rx) = channel::unbounded::<(u8, u8, bool)>();
-    let (hw_requests_tx, _) = channel::unbounded::<(u8, u8)>();
-    let (hw_floor_sensor_tx, _) = channel::unbounded::<u8>();
-    let (_, hw_floor_indicator_rx) = channel::unbounded::<u8>();
-    let (_, hw_door_light_rx) = channel::unbounded::<bool>();
-    let (hw_emergency_halt_tx, _) = channel::unbounded::<bool>();
-    let (hw_obstruction_tx, _) = channel::unbounded::<bool>();
+    let (hw_event_tx, hw_event_rx) = channel::bounded::<HardwareEvent>(HARDWARE_CHANNEL_CAPACITY);
+    let (hw_command_tx, hw_command_rx) =
+        channel::bounded::<HardwareCommand>(HARDWARE_CHANNEL_CAPACITY);
+
+    // elevator
+    let (elevator_availability_tx, elevator_availability_rx) = channel::unbounded::<bool>();
+    let (hall_request_tx, hall_request_rx) = channel::unbounded::<(u8, Direction)>();
+    let (hall_call_tx, hall_call_rx) = channel::unbounded::<Call>();
+    let (hall_call_released_tx, hall_call_released_rx) = channel::unbounded::<Call>();
+    let (hall_call_served_tx, hall_call_served_rx) = channel::unbounded::<Call>();
+    let (cab_orders_tx, cab_orders_rx) = channel::unbounded::<Vec<Command>>();
+    let (restored_commands_tx, restored_commands_rx) = channel::unbounded::<Vec<Command>>();
+    let (elevator_status_tx, elevator_status_rx) = channel::unbounded::<ElevatorStatus>();
+    let (peer_states_tx, peer_states_rx) = channel::unbounded::<Vec<PeerState>>();
+
+    // network
+    let (network_availability_tx, network_availability_rx) = channel::unbounded::<bool>();
+    let (network_peer_event_tx, network_peer_event_rx) = channel::unbounded::<PeerEvent>();
+    let (network_outbound_tx, network_outbound_rx) = channel::unbounded::<Message>();
+    let (network_inbound_tx, network_inbound_rx) = channel::unbounded::<Message>();
 
     let elevator_driver = ElevatorDriver::new(
         &config.hardware,
-        hw_motor_direction_rx,
-        hw_button_light_rx,
-        hw_requests_tx,
-        hw_floor_sensor_tx,
-        hw_floor_indicator_rx,
-        hw_door_light_rx,
-        hw_emergency_halt_tx,
-        hw_obstruction_tx,
+        hw_event_tx,
+        hw_command_rx,
+        driver_tunables_rx,
+        hw_terminate_rx.clone(),
+    );
+
+    let order_queue = OrderQueue::with_journal(
+        &config.storage.journal_path,
+        config.storage.journal_compaction_threshold,
+    )?;
+
+    // validation has made sure the scheduler exists
+    let scheduler = scheduler::create_scheduler(
+        &config.scheduling.scheduler,
+        config.hardware.num_floors,
+        Duration::from_millis(config.hardware.door_open_duration_milliseconds),
+        Duration::from_millis(config.hardware.door_stuck_timeout_milliseconds),
+    )
+    .ok_or_else(|| format!("Unknown scheduler {}", config.scheduling.scheduler))?;
+    info!("Scheduling orders with the {} scheduler", scheduler.name());
+
+    let elevator_fsm = ElevatorFsm::new(
+        &config.hardware,
+        get_clock_uuid(),
+        scheduler,
+        order_queue,
+        hw_command_tx.clone(),
+        hw_event_rx,
+        hall_request_tx,
+        hall_call_rx,
+        hall_call_released_rx,
+        hall_call_served_tx,
+        cab_orders_tx,
+        restored_commands_rx,
+        fsm_tunables_rx,
+        elevator_availability_tx,
+        elevator_status_tx,
+        peer_states_rx,
+        hw_terminate_rx.clone(),
+    );
+
+    let network_node = NetworkNode::new(
+        &config.network,
+        node_id,
+        network_availability_rx,
+        network_peer_event_tx,
+        network_outbound_rx,
+        network_inbound_tx,
+        hw_terminate_rx.clone(),
+    )?;
+
+    let coordinator = Coordinator::new(
+        &config,
+        node_id,
+        hall_request_rx,
+        hall_call_tx,
+        hall_call_served_rx,
+        hall_call_released_tx,
+        cab_orders_rx,
+        restored_commands_tx,
+        hw_command_tx,
+        elevator_availability_rx,
+        elevator_status_rx,
+        peer_states_tx,
+        coordinator_tunables_rx,
+        network_availability_tx,
+        network_peer_event_rx,
+        network_inbound_rx,
+        network_outbound_tx,
+        hw_terminate_rx.clone(),
+    );
+
+    let (config_path, profile) = (cli.config.clone(), cli.profile.clone());
+    let config_watcher = ConfigWatcher::new(
+        &config_path,
+        profile.as_deref(),
+        config,
+        Box::new(move |config| cli.apply(config)),
+        Duration::from_secs(1),
+        vec![driver_tunables_tx, fsm_tunables_tx, coordinator_tunables_tx],
         hw_terminate_rx,
     );
 
-    // TODO: spawn pass instance to thread and run
     let driver_thread = thread::Builder::new().name("driver".into());
-    driver_thread
+    let driver_handle = driver_thread
         .spawn(move || {
             elevator_driver
                 .expect("Failed to initiate ElevatorDriver")
@@ -45,7 +153,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         })
         .unwrap();
 
-    loop {
-        thread::sleep(std::time::Duration::from_secs(1))
-    }
+    let fsm_thread = thread::Builder::new().name("fsm".into());
+    let fsm_handle = fsm_thread.spawn(move || elevator_fsm.run()).unwrap();
+
+    let network_thread = thread::Builder::new().name("network".into());
+    let network_handle = network_thread.spawn(move || network_node.run()).unwrap();
+
+    let coordinator_thread = thread::Builder::new().name("coordinator".into());
+    let coordinator_handle = coordinator_thread.spawn(move || coordinator.run()).unwrap();
+
+    let config_thread = thread::Builder::new().name("config".into());
+    let config_handle = config_thread.spawn(move || config_watcher.run()).unwrap();
+
+    let _ = driver_handle.join();
+    let _ = fsm_handle.join();
+    let _ = network_handle.join();
+    let _ = coordinator_handle.join();
+    let _ = config_handle.join();
+    Ok(())
 }
@@ -0,0 +1,919 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel as channel;
+use log::{debug, error, info};
+use uhlc::ID;
+use uuid::Uuid;
+
+use super::hall_calls::{HallCallEntry, HallCallState, HallCallTable};
+use super::message::Message;
+use super::peers::PeerEvent;
+use crate::clock::current_timestamp;
+use crate::config::{Config, Tunables};
+use crate::elevator::HardwareCommand;
+use crate::queue::assigner::{ElevatorStatus, HallCallAssigner, ESTIMATED_TRAVEL_TIME};
+use crate::queue::scheduler::PeerState;
+use crate::queue::{Call, Command, Direction};
+
+// Keeps this node's replicated state in agreement with its peers
+// Hall button presses from the elevator are registered in the replicated hall call table, and
+// the lights are only turned on once every live peer has acknowledged the call. Confirmed calls
+// are assigned by the estimated time each elevator needs to serve them, and the ones assigned
+// to this node are handed to the elevator, which reports back once served.
+// Each node also keeps a backup of its peers' cab commands, so an elevator restarting with a
+// wiped disk can ask for its own commands back.
+pub struct Coordinator {
+    node_id: ID,
+    is_available: bool,
+    peers: HashMap<ID, bool>,       // live peers and their availability
+    status: Option<ElevatorStatus>, // unknown until the elevator has homed
+    peer_statuses: HashMap<ID, ElevatorStatus>,
+    published_peer_states: Vec<PeerState>, // last sent to the elevator
+    assigner: HallCallAssigner,
+    hall_calls: HallCallTable,
+    lights: Vec<bool>,
+    delivered: HashSet<Uuid>,               // calls handed to the elevator
+    cab_orders: Option<Vec<Command>>,       // unknown until the elevator reports its commands
+    cab_backups: HashMap<ID, Vec<Command>>, // cab commands of peers, keyed by node ID
+    sync_interval: Duration,
+    order_expiry_seconds: u64, // given to the hall calls requested here
+    hall_request_rx: channel::Receiver<(u8, Direction)>,
+    hall_call_tx: channel::Sender<Call>,
+    hall_call_served_rx: channel::Receiver<Call>,
+    hall_call_released_tx: channel::Sender<Call>,
+    cab_orders_rx: channel::Receiver<Vec<Command>>,
+    restored_commands_tx: channel::Sender<Vec<Command>>,
+    hw_command_tx: channel::Sender<HardwareCommand>,
+    availability_rx: channel::Receiver<bool>,
+    status_rx: channel::Receiver<ElevatorStatus>,
+    peer_states_tx: channel::Sender<Vec<PeerState>>,
+    tunables_rx: channel::Receiver<Tunables>,
+    network_availability_tx: channel::Sender<bool>,
+    peer_event_rx: channel::Receiver<PeerEvent>,
+    inbound_rx: channel::Receiver<Message>,
+    outbound_tx: channel::Sender<Message>,
+    terminate_rx: channel::Receiver<()>,
+}
+
+impl Coordinator {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        config: &Config,
+        node_id: ID,
+        hall_request_rx: channel::Receiver<(u8, Direction)>,
+        hall_call_tx: channel::Sender<Call>,
+        hall_call_served_rx: channel::Receiver<Call>,
+        hall_call_released_tx: channel::Sender<Call>,
+        cab_orders_rx: channel::Receiver<Vec<Command>>,
+        restored_commands_tx: channel::Sender<Vec<Command>>,
+        hw_command_tx: channel::Sender<HardwareCommand>,
+        availability_rx: channel::Receiver<bool>,
+        status_rx: channel::Receiver<ElevatorStatus>,
+        peer_states_tx: channel::Sender<Vec<PeerState>>,
+        tunables_rx: channel::Receiver<Tunables>,
+        network_availability_tx: channel::Sender<bool>,
+        peer_event_rx: channel::Receiver<PeerEvent>,
+        inbound_rx: channel::Receiver<Message>,
+        outbound_tx: channel::Sender<Message>,
+        terminate_rx: channel::Receiver<()>,
+    ) -> Coordinator {
+        let hall_calls =
+            HallCallTable::new(config.hardware.num_floors, node_id, current_timestamp());
+        let lights = vec![false; hall_calls.entries().len()];
+
+        Coordinator {
+            node_id,
+            is_available: true,
+            peers: HashMap::new(),
+            status: None,
+            peer_statuses: HashMap::new(),
+            published_peer_states: Vec::new(),
+            assigner: HallCallAssigner::new(
+                config.hardware.num_floors,
+                ESTIMATED_TRAVEL_TIME,
+                Duration::from_millis(config.hardware.door_open_duration_milliseconds),
+            ),
+            hall_calls,
+            lights,
+            delivered: HashSet::new(),
+            cab_orders: None,
+            cab_backups: HashMap::new(),
+            sync_interval: Duration::from_millis(config.network.heartbeat_interval_milliseconds),
+            order_expiry_seconds: config.hardware.order_expiry_seconds,
+            hall_request_rx,
+            hall_call_tx,
+            hall_call_served_rx,
+            hall_call_released_tx,
+            cab_orders_rx,
+            restored_commands_tx,
+            hw_command_tx,
+            availability_rx,
+            status_rx,
+            peer_states_tx,
+            tunables_rx,
+            network_availability_tx,
+            peer_event_rx,
+            inbound_rx,
+            outbound_tx,
+            terminate_rx,
+        }
+    }
+
+    pub fn run(mut self) {
+        info!("Starting coordinator for node {}", self.node_id);
+
+        let mut next_sync = Instant::now();
+        loop {
+            let sync_timer = channel::at(next_sync);
+
+            channel::select! {
+              recv(self.hall_request_rx) -> msg => {
+                match msg {
+                  Ok((floor, direction)) => self.on_hall_request(floor, direction),
+                  Err(error) => {
+                    error!("Lost connection to hall request channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.hall_call_served_rx) -> msg => {
+                match msg {
+                  Ok(call) => self.on_hall_call_served(call),
+                  Err(error) => {
+                    error!("Lost connection to served hall call channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.cab_orders_rx) -> msg => {
+                match msg {
+                  Ok(commands) => self.on_cab_orders(commands),
+                  Err(error) => {
+                    error!("Lost connection to cab order channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.availability_rx) -> msg => {
+                match msg {
+                  Ok(is_available) => self.on_availability(is_available),
+                  Err(error) => {
+                    error!("Lost connection to availability channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.status_rx) -> msg => {
+                match msg {
+                  Ok(status) => self.on_status(status),
+                  Err(error) => {
+                    error!("Lost connection to elevator status channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.peer_event_rx) -> msg => {
+                match msg {
+                  Ok(event) => self.on_peer_event(event),
+                  Err(error) => {
+                    error!("Lost connection to peer event channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.inbound_rx) -> msg => {
+                match msg {
+                  Ok(message) => self.on_message(message),
+                  Err(error) => {
+                    error!("Lost connection to inbound message channel {}", error);
+                    break;
+                  }
+                }
+              }
+
+              recv(self.tunables_rx) -> msg => {
+                if let Ok(tunables) = msg {
+                  self.on_tunables(tunables);
+                }
+              }
+
+              // resend periodically so lost packets only delay agreement
+              recv(sync_timer) -> _ => {
+                self.broadcast_hall_calls();
+                self.broadcast_cab_orders();
+                self.broadcast_status();
+                next_sync = Instant::now() + self.sync_interval;
+              }
+
+              recv(self.terminate_rx) -> _ => {
+                break;
+              }
+            }
+        }
+    }
+
+    fn on_tunables(&mut self, tunables: Tunables) {
+        self.order_expiry_seconds = tunables.order_expiry_seconds;
+    }
+
+    fn on_hall_request(&mut self, floor: u8, direction: Direction) {
+        let is_changed = self.hall_calls.request(
+            floor,
+            direction,
+            self.order_expiry_seconds,
+            current_timestamp(),
+        );
+        if is_changed {
+            debug!(
+                "Requesting hall call at floor {} going {:?}",
+                floor, direction
+            );
+        }
+        // a repeated press of a call still waiting on the peers asks them again
+        let is_unconfirmed = self
+            .hall_calls
+            .get(floor, direction)
+            .is_some_and(|entry| entry.state == HallCallState::Unconfirmed);
+        self.update(is_changed || is_unconfirmed);
+    }
+
+    fn on_hall_call_served(&mut self, call: Call) {
+        self.delivered.remove(&call.id);
+        let is_changed =
+            self.hall_calls
+                .clear(call.target_floor, call.direction, current_timestamp());
+        self.update(is_changed);
+    }
+
+    fn on_cab_orders(&mut self, commands: Vec<Command>) {
+        self.cab_orders = Some(commands);
+        self.broadcast_cab_orders();
+    }
+
+    fn on_availability(&mut self, is_available: bool) {
+        self.is_available = is_available;
+        let _ = self.network_availability_tx.send(is_available);
+
+        // let available peers take over while this elevator cannot serve
+        let released = match is_available {
+            true => Vec::new(),
+            false => self.hall_calls.release(&self.node_id, current_timestamp()),
+        };
+        // the elevator drops them too, or the same call would be served twice
+        for call in &released {
+            self.delivered.remove(&call.id);
+            let _ = self.hall_call_released_tx.send(call.clone());
+        }
+        self.update(!released.is_empty());
+    }
+
+    fn on_status(&mut self, status: ElevatorStatus) {
+        self.status = Some(status);
+        self.broadcast_status();
+        // a moving elevator may now be the better choice for a waiting call
+        self.update(false);
+    }
+
+    fn on_peer_event(&mut self, event: PeerEvent) {
+        let is_changed = match event {
+            // share the table right away so the newcomer catches up
+            PeerEvent::PeerJoined(peer) => {
+                self.peers.insert(peer.node_id, peer.is_available);
+                self.share_cab_orders();
+                true
+            }
+            PeerEvent::PeerLost(node_id) => {
+                self.peers.remove(&node_id);
+                self.peer_statuses.remove(&node_id);
+                !self
+                    .hall_calls
+                    .release(&node_id, current_timestamp())
+                    .is_empty()
+            }
+            PeerEvent::AvailabilityChanged(peer) => {
+                self.peers.insert(peer.node_id, peer.is_available);
+                !peer.is_available
+                    && !self
+                        .hall_calls
+                        .release(&peer.node_id, current_timestamp())
+                        .is_empty()
+            }
+        };
+        self.update(is_changed);
+    }
+
+    fn on_message(&mut self, message: Message) {
+        match message {
+            Message::HallCalls { entries, .. } => {
+                let is_changed = self.hall_calls.merge(&entries);
+                self.update(is_changed);
+            }
+            Message::CabOrders {
+                node_id,
+                owner,
+                commands,
+            } => self.on_cab_orders_message(node_id, owner, commands),
+            Message::CabOrdersRequest { node_id } => self.on_cab_orders_request(node_id),
+            Message::ElevatorStatus { node_id, status } => {
+                self.peer_statuses.insert(node_id, status);
+                self.update(false);
+            }
+            Message::Heartbeat(_) => {}
+        }
+    }
+
+    fn on_cab_orders_message(&mut self, node_id: ID, owner: ID, commands: Vec<Command>) {
+        if owner == self.node_id {
+            // a peer is handing back our commands, the elevator skips the ones it already has
+            if !commands.is_empty() {
+                info!(
+                    "Received backup of {} cab orders from node {}",
+                    commands.len(),
+                    node_id
+                );
+                let _ = self.restored_commands_tx.send(commands);
+            }
+        } else if owner == node_id {
+            self.cab_backups.insert(owner, commands);
+        }
+    }
+
+    fn on_cab_orders_request(&self, node_id: ID) {
+        if let Some(commands) = self.cab_backups.get(&node_id) {
+            debug!(
+                "Restoring {} cab orders to node {}",
+                commands.len(),
+                node_id
+            );
+            let _ = self.outbound_tx.send(Message::CabOrders {
+                node_id: self.node_id,
+                owner: node_id,
+                commands: commands.clone(),
+            });
+        }
+    }
+
+    // Back up our commands on a new peer, or ask for them back if we have none yet
+    // Until the elevator has reported its commands they may only exist on the peers, so
+    // sending an empty set would overwrite their backup.
+    fn share_cab_orders(&self) {
+        if self.cab_orders.is_some() {
+            self.broadcast_cab_orders();
+        } else {
+            let _ = self.outbound_tx.send(Message::CabOrdersRequest {
+                node_id: self.node_id,
+            });
+        }
+    }
+
+    // Advance the table after a change, and share it with peers if anything changed
+    fn update(&mut self, is_changed: bool) {
+        let live_peers: Vec<ID> = self.peers.keys().copied().collect();
+        let mut is_changed =
+            self.hall_calls.confirm(&live_peers, current_timestamp()) || is_changed;
+        is_changed = self.claim_hall_calls() || is_changed;
+
+        self.deliver_hall_calls();
+        self.update_lights();
+        self.publish_peer_states();
+
+        if is_changed {
+            self.broadcast_hall_calls();
+        }
+    }
+
+    fn claim_hall_calls(&mut self) -> bool {
+        let mut is_changed = false;
+        for (floor, direction) in self.responsible_calls() {
+            is_changed |= self
+                .hall_calls
+                .claim(floor, direction, current_timestamp())
+                .is_some();
+        }
+        is_changed
+    }
+
+    // Confirmed calls this node should serve
+    // Assigned by time to serve when the state of every candidate elevator is known, otherwise
+    // by the simple rule of is_responsible_for, e.g. while a peer has just joined.
+    fn responsible_calls(&self) -> Vec<(u8, Direction)> {
+        let confirmed: Vec<&HallCallEntry> = self
+            .hall_calls
+            .entries()
+            .iter()
+            .filter(|entry| entry.state == HallCallState::Confirmed)
+            .collect();
+        if confirmed.is_empty() {
+            return Vec::new();
+        }
+
+        let statuses: Option<BTreeMap<ID, ElevatorStatus>> = self
+            .candidates()
+            .into_iter()
+            .map(|node_id| {
+                self.status_of(&node_id)
+                    .map(|status| (node_id, status.clone()))
+            })
+            .collect();
+        let Some(statuses) = statuses else {
+            return confirmed
+                .into_iter()
+                .filter(|entry| self.is_responsible_for(entry))
+                .map(|entry| (entry.floor, entry.direction))
+                .collect();
+        };
+
+        let calls: Vec<Call> = confirmed
+            .iter()
+            .filter_map(|entry| entry.call.clone())
+            .collect();
+        self.assigner
+            .assign(&statuses, &calls)
+            .remove(&self.node_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|call| (call.target_floor, call.direction))
+            .collect()
+    }
+
+    fn status_of(&self, node_id: &ID) -> Option<&ElevatorStatus> {
+        if *node_id == self.node_id {
+            self.status.as_ref()
+        } else {
+            self.peer_statuses.get(node_id)
+        }
+    }
+
+    // Nodes that may serve hall calls, the available ones or only this one if none is
+    fn candidates(&self) -> Vec<ID> {
+        let mut candidates: Vec<ID> = self
+            .peers
+            .iter()
+            .filter(|(_, is_available)| **is_available)
+            .map(|(node_id, _)| *node_id)
+            .collect();
+        // somebody has to serve the call, even if no elevator is fully available
+        if self.is_available || candidates.is_empty() {
+            candidates.push(self.node_id);
+        }
+        candidates
+    }
+
+    // The node that registered the call serves it if it can, otherwise the lowest available ID
+    fn is_responsible_for(&self, entry: &HallCallEntry) -> bool {
+        let candidates = self.candidates();
+        let chosen = entry
+            .requested_by
+            .filter(|node_id| candidates.contains(node_id))
+            .or_else(|| candidates.iter().min().copied());
+        chosen == Some(self.node_id)
+    }
+
+    fn deliver_hall_calls(&mut self) {
+        for entry in self.hall_calls.entries() {
+            if entry.state != HallCallState::Serving || entry.assignee != Some(self.node_id) {
+                continue;
+            }
+            if let Some(call) = &entry.call {
+                if self.delivered.insert(call.id) {
+                    let _ = self.hall_call_tx.send(call.clone());
+                }
+            }
+        }
+    }
+
+    fn update_lights(&mut self) {
+        for (entry, is_lit) in self.hall_calls.entries().iter().zip(self.lights.iter_mut()) {
+            if entry.is_lit() != *is_lit {
+                *is_lit = entry.is_lit();
+                let _ = self.hw_command_tx.send(HardwareCommand::SetButtonLight {
+                    floor: entry.floor,
+                    kind: entry.direction.into(),
+                    is_lit: *is_lit,
+                });
+            }
+        }
+    }
+
+    // Share what the live peers are doing with the elevator, for its scheduler
+    fn publish_peer_states(&mut self) {
+        let mut peer_states: Vec<PeerState> = self
+            .peer_statuses
+            .iter()
+            .filter_map(|(node_id, status)| {
+                self.peers.get(node_id).map(|is_available| PeerState {
+                    node_id: *node_id,
+                    is_available: *is_available,
+                    status: status.clone(),
+                })
+            })
+            .collect();
+        peer_states.sort_by_key(|peer| peer.node_id);
+
+        if peer_states != self.published_peer_states {
+            let _ = self.peer_states_tx.send(peer_states.clone());
+            self.published_peer_states = peer_states;
+        }
+    }
+
+    fn broadcast_cab_orders(&self) {
+        if let Some(commands) = &self.cab_orders {
+            let _ = self.outbound_tx.send(Message::CabOrders {
+                node_id: self.node_id,
+                owner: self.node_id,
+                commands: commands.clone(),
+            });
+        }
+    }
+
+    fn broadcast_status(&self) {
+        if let Some(status) = &self.status {
+            let _ = self.outbound_tx.send(Message::ElevatorStatus {
+                node_id: self.node_id,
+                status: status.clone(),
+            });
+        }
+    }
+
+    fn broadcast_hall_calls(&self) {
+        let _ = self.outbound_tx.send(Message::HallCalls {
+            node_id: self.node_id,
+            entries: self.hall_calls.entries().to_vec(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{init_clock_with_random_id, TimestampExt};
+    use crate::config::{
+        HaltPolicy, HardwareConfig, NetworkConfig, SchedulingConfig, StorageConfig,
+    };
+    use crate::elevator::{ButtonKind, ElevatorState};
+    use crate::network::peers::Peer;
+
+    struct TestHarness {
+        coordinator: Coordinator,
+        hall_call_rx: channel::Receiver<Call>,
+        hall_call_released_rx: channel::Receiver<Call>,
+        restored_commands_rx: channel::Receiver<Vec<Command>>,
+        command_rx: channel::Receiver<HardwareCommand>,
+        peer_states_rx: channel::Receiver<Vec<PeerState>>,
+        outbound_rx: channel::Receiver<Message>,
+    }
+
+    fn setup_test_clock() {
+        let _ = init_clock_with_random_id();
+    }
+
+    fn id(value: u8) -> ID {
+        ID::try_from(value).unwrap()
+    }
+
+    fn setup_coordinator(node_id: u8) -> TestHarness {
+        setup_test_clock();
+
+        let config = Config {
+            hardware: HardwareConfig {
+                num_floors: 4,
+                driver_address: "localhost".to_string(),
+                driver_port: 15657,
+                driver_channel_poll_timeout_milliseconds: 25,
+                button_debounce_milliseconds: 20,
+                door_open_duration_milliseconds: 3000,
+                door_stuck_timeout_milliseconds: 20000,
+                homing_timeout_milliseconds: 10000,
+                halt_policy: HaltPolicy::StopAndKeepOrders,
+                order_expiry_seconds: 60,
+            },
+            network: NetworkConfig {
+                address: "127.255.255.255".to_string(),
+                port: 1234,
+                heartbeat_interval_milliseconds: 100,
+                peer_timeout_milliseconds: 1000,
+            },
+            storage: StorageConfig {
+                journal_path: "data/cab_orders.journal".to_string(),
+                journal_compaction_threshold: 1000,
+            },
+            scheduling: SchedulingConfig::default(),
+        };
+
+        let (_, hall_request_rx) = channel::unbounded();
+        let (hall_call_tx, hall_call_rx) = channel::unbounded();
+        let (_, hall_call_served_rx) = channel::unbounded();
+        let (hall_call_released_tx, hall_call_released_rx) = channel::unbounded();
+        let (_, cab_orders_rx) = channel::unbounded();
+        let (restored_commands_tx, restored_commands_rx) = channel::unbounded();
+        let (command_tx, command_rx) = channel::unbounded();
+        let (_, availability_rx) = channel::unbounded();
+        let (_, status_rx) = channel::unbounded();
+        let (peer_states_tx, peer_states_rx) = channel::unbounded();
+        let (_, tunables_rx) = channel::unbounded();
+        let (network_availability_tx, _) = channel::unbounded();
+        let (_, peer_event_rx) = channel::unbounded();
+        let (_, inbound_rx) = channel::unbounded();
+        let (outbound_tx, outbound_rx) = channel::unbounded();
+        let (_, terminate_rx) = channel::unbounded();
+
+        let coordinator = Coordinator::new(
+            &config,
+            id(node_id),
+            hall_request_rx,
+            hall_call_tx,
+            hall_call_served_rx,
+            hall_call_released_tx,
+            cab_orders_rx,
+            restored_commands_tx,
+            command_tx,
+            availability_rx,
+            status_rx,
+            peer_states_tx,
+            tunables_rx,
+            network_availability_tx,
+            peer_event_rx,
+            inbound_rx,
+            outbound_tx,
+            terminate_rx,
+        );
+
+        TestHarness {
+            coordinator,
+            hall_call_rx,
+            hall_call_released_rx,
+            restored_commands_rx,
+            command_rx,
+            peer_states_rx,
+            outbound_rx,
+        }
+    }
+
+    fn drain<T>(rx: &channel::Receiver<T>) -> Vec<T> {
+        rx.try_iter().collect()
+    }
+
+    fn lights(harness: &TestHarness) -> Vec<(u8, ButtonKind, bool)> {
+        drain(&harness.command_rx)
+            .into_iter()
+            .filter_map(|command| match command {
+                HardwareCommand::SetButtonLight {
+                    floor,
+                    kind,
+                    is_lit,
+                } => Some((floor, kind, is_lit)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Deliver everything one node has broadcast to the other
+    fn forward(from: &TestHarness, to: &mut TestHarness) {
+        for message in drain(&from.outbound_rx) {
+            to.coordinator.on_message(message);
+        }
+    }
+
+    fn join(first: &mut TestHarness, second: &mut TestHarness) {
+        let first_id = first.coordinator.node_id;
+        let second_id = second.coordinator.node_id;
+        for (harness, node_id) in [(first, second_id), (second, first_id)] {
+            harness
+                .coordinator
+                .on_peer_event(PeerEvent::PeerJoined(Peer {
+                    node_id,
+                    is_available: true,
+                    last_timestamp: current_timestamp(),
+                }));
+        }
+    }
+
+    #[test]
+    fn test_alone_lights_and_serves_immediately() {
+        let mut harness = setup_coordinator(1);
+
+        harness.coordinator.on_hall_request(2, Direction::Up);
+        assert_eq!(lights(&harness), vec![(2, ButtonKind::HallUp, true)]);
+
+        let calls = drain(&harness.hall_call_rx);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].target_floor, 2);
+
+        harness.coordinator.on_hall_call_served(calls[0].clone());
+        assert_eq!(lights(&harness), vec![(2, ButtonKind::HallUp, false)]);
+        assert!(drain(&harness.hall_call_rx).is_empty());
+    }
+
+    #[test]
+    fn test_hall_calls_expire_after_the_reloaded_time() {
+        let mut harness = setup_coordinator(1);
+        let mut tunables = Config::default().tunables();
+        tunables.order_expiry_seconds = 5;
+        harness.coordinator.on_tunables(tunables);
+
+        harness.coordinator.on_hall_request(2, Direction::Up);
+        let calls = drain(&harness.hall_call_rx);
+        assert_eq!(
+            calls[0].expires_at,
+            calls[0].created_at.add_duration(Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn test_light_waits_for_peer_acknowledgement() {
+        let mut first = setup_coordinator(1);
+        let mut second = setup_coordinator(2);
+        join(&mut first, &mut second);
+        drain(&first.outbound_rx);
+        drain(&second.outbound_rx);
+
+        first.coordinator.on_hall_request(1, Direction::Down);
+        assert!(lights(&first).is_empty());
+        assert!(drain(&first.hall_call_rx).is_empty());
+
+        // the peer has seen both acknowledgements once it merges, the requester once it hears back
+        forward(&first, &mut second);
+        assert_eq!(lights(&second), vec![(1, ButtonKind::HallDown, true)]);
+        forward(&second, &mut first);
+        assert_eq!(lights(&first), vec![(1, ButtonKind::HallDown, true)]);
+        forward(&first, &mut second);
+        assert!(lights(&second).is_empty());
+
+        // the requesting node serves its own call
+        assert_eq!(drain(&first.hall_call_rx).len(), 1);
+        assert!(drain(&second.hall_call_rx).is_empty());
+    }
+
+    #[test]
+    fn test_repeated_request_asks_peers_again() {
+        let mut first = setup_coordinator(1);
+        let mut second = setup_coordinator(2);
+        join(&mut first, &mut second);
+        drain(&first.outbound_rx);
+
+        // the first broadcast is lost on the way
+        first.coordinator.on_hall_request(2, Direction::Up);
+        assert_eq!(drain(&first.outbound_rx).len(), 1);
+
+        first.coordinator.on_hall_request(2, Direction::Up);
+        forward(&first, &mut second);
+        assert_eq!(lights(&second), vec![(2, ButtonKind::HallUp, true)]);
+
+        // once confirmed, pressing again has nothing to ask
+        forward(&second, &mut first);
+        drain(&first.outbound_rx);
+        first.coordinator.on_hall_request(2, Direction::Up);
+        assert!(drain(&first.outbound_rx).is_empty());
+    }
+
+    fn idle_at(floor: u8) -> ElevatorStatus {
+        ElevatorStatus {
+            floor,
+            direction: None,
+            behaviour: ElevatorState::Idle,
+            commands: Vec::new(),
+            calls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_call_goes_to_the_elevator_serving_it_first() {
+        let mut first = setup_coordinator(1);
+        let mut second = setup_coordinator(2);
+        join(&mut first, &mut second);
+        first.coordinator.on_status(idle_at(0));
+        second.coordinator.on_status(idle_at(3));
+        forward(&first, &mut second);
+        forward(&second, &mut first);
+
+        // requested on the first node, but the second one is already there
+        first.coordinator.on_hall_request(3, Direction::Down);
+        forward(&first, &mut second);
+        forward(&second, &mut first);
+        forward(&first, &mut second);
+        assert!(drain(&first.hall_call_rx).is_empty());
+        let calls = drain(&second.hall_call_rx);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].target_floor, 3);
+    }
+
+    #[test]
+    fn test_peer_states_are_shared_with_the_elevator() {
+        let mut first = setup_coordinator(1);
+        let mut second = setup_coordinator(2);
+        second.coordinator.on_status(idle_at(3));
+        // a status from a node that has not joined yet is kept back
+        forward(&second, &mut first);
+        assert!(drain(&first.peer_states_rx).is_empty());
+
+        join(&mut first, &mut second);
+        forward(&second, &mut first);
+        let published = drain(&first.peer_states_rx);
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].len(), 1);
+        assert_eq!(published[0][0].node_id, id(2));
+        assert_eq!(published[0][0].status, idle_at(3));
+
+        // the same view is not sent again
+        second.coordinator.on_status(idle_at(3));
+        forward(&second, &mut first);
+        assert!(drain(&first.peer_states_rx).is_empty());
+
+        first.coordinator.on_peer_event(PeerEvent::PeerLost(id(2)));
+        assert_eq!(drain(&first.peer_states_rx), vec![Vec::new()]);
+    }
+
+    #[test]
+    fn test_calls_of_lost_peer_are_taken_over() {
+        let mut first = setup_coordinator(1);
+        let mut second = setup_coordinator(2);
+        join(&mut first, &mut second);
+
+        first.coordinator.on_hall_request(3, Direction::Down);
+        forward(&first, &mut second);
+        forward(&second, &mut first);
+        forward(&first, &mut second);
+        assert_eq!(drain(&first.hall_call_rx).len(), 1);
+        assert!(drain(&second.hall_call_rx).is_empty());
+
+        second.coordinator.on_peer_event(PeerEvent::PeerLost(id(1)));
+        let calls = drain(&second.hall_call_rx);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].target_floor, 3);
+    }
+
+    #[test]
+    fn test_unavailable_elevator_gives_up_its_calls() {
+        let mut first = setup_coordinator(1);
+        let mut second = setup_coordinator(2);
+        join(&mut first, &mut second);
+
+        first.coordinator.on_hall_request(1, Direction::Up);
+        forward(&first, &mut second);
+        forward(&second, &mut first);
+        let calls = drain(&first.hall_call_rx);
+        assert_eq!(calls.len(), 1);
+
+        // the elevator drops the call, so only the peer taking over serves it
+        first.coordinator.on_availability(false);
+        assert_eq!(drain(&first.hall_call_released_rx), calls);
+        assert!(drain(&first.hall_call_rx).is_empty());
+        second
+            .coordinator
+            .on_peer_event(PeerEvent::AvailabilityChanged(Peer {
+                node_id: id(1),
+                is_available: false,
+                last_timestamp: current_timestamp(),
+            }));
+        forward(&first, &mut second);
+        assert_eq!(drain(&second.hall_call_rx), calls);
+    }
+
+    #[test]
+    fn test_peer_restores_cab_orders_after_wipe() {
+        let mut first = setup_coordinator(1);
+        let mut second = setup_coordinator(2);
+        join(&mut first, &mut second);
+
+        // neither node knows its commands yet, so both ask instead of sharing
+        assert!(matches!(
+            drain(&first.outbound_rx).first(),
+            Some(Message::CabOrdersRequest { .. })
+        ));
+        drain(&second.outbound_rx);
+
+        let commands = vec![Command::new(1), Command::new(3)];
+        first.coordinator.on_cab_orders(commands.clone());
+        forward(&first, &mut second);
+
+        // the first node comes back with a wiped disk and asks for its commands
+        let mut restarted = setup_coordinator(1);
+        join(&mut restarted, &mut second);
+        drain(&second.outbound_rx);
+        forward(&restarted, &mut second);
+        forward(&second, &mut restarted);
+        assert_eq!(drain(&restarted.restored_commands_rx), vec![commands]);
+    }
+
+    #[test]
+    fn test_backup_is_only_taken_from_owner() {
+        let mut harness = setup_coordinator(1);
+
+        harness.coordinator.on_message(Message::CabOrders {
+            node_id: id(2),
+            owner: id(3),
+            commands: vec![Command::new(2)],
+        });
+        harness
+            .coordinator
+            .on_message(Message::CabOrdersRequest { node_id: id(3) });
+        assert!(drain(&harness.outbound_rx).is_empty());
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use uhlc::{Timestamp, ID};
+
+use crate::queue::{Call, Direction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HallCallState {
+    // No call, the light is off
+    None,
+    // Requested, waiting for every live peer to acknowledge
+    Unconfirmed,
+    // Acknowledged by every live peer, the light is on
+    Confirmed,
+    // An elevator has taken responsibility for the call
+    Serving,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HallCallEntry {
+    pub floor: u8,
+    pub direction: Direction,
+    pub state: HallCallState,
+    pub call: Option<Call>,
+    pub requested_by: Option<ID>,
+    pub assignee: Option<ID>,
+    pub acknowledged_by: BTreeSet<ID>,
+    pub updated_at: Timestamp, // HLC time of the last state transition
+    pub is_initial: bool,      // never transitioned, so it holds nothing to replicate
+}
+
+impl HallCallEntry {
+    fn new(floor: u8, direction: Direction, now: Timestamp) -> Self {
+        Self {
+            floor,
+            direction,
+            state: HallCallState::None,
+            call: None,
+            requested_by: None,
+            assignee: None,
+            acknowledged_by: BTreeSet::new(),
+            updated_at: now,
+            is_initial: true,
+        }
+    }
+
+    // Whether the hall button light should be on for this entry
+    pub fn is_lit(&self) -> bool {
+        matches!(
+            self.state,
+            HallCallState::Confirmed | HallCallState::Serving
+        )
+    }
+
+    fn transition(&mut self, state: HallCallState, now: Timestamp) {
+        self.state = state;
+        self.updated_at = now;
+        self.is_initial = false;
+    }
+}
+
+// Hall calls replicated across all nodes, one entry per floor and direction
+// Conflicting updates are resolved by the HLC timestamp of the transition, newest wins.
+#[derive(Debug, Clone)]
+pub struct HallCallTable {
+    node_id: ID,
+    entries: Vec<HallCallEntry>,
+}
+
+impl HallCallTable {
+    pub fn new(num_floors: u8, node_id: ID, now: Timestamp) -> Self {
+        let entries = (0..num_floors)
+            .flat_map(|floor| {
+                [
+                    HallCallEntry::new(floor, Direction::Up, now),
+                    HallCallEntry::new(floor, Direction::Down, now),
+                ]
+            })
+            .collect();
+
+        Self { node_id, entries }
+    }
+
+    pub fn get(&self, floor: u8, direction: Direction) -> Option<&HallCallEntry> {
+        self.index(floor, direction)
+            .map(|index| &self.entries[index])
+    }
+
+    pub fn entries(&self) -> &[HallCallEntry] {
+        &self.entries
+    }
+
+    // Register a button press on this node, returns whether the table changed
+    pub fn request(
+        &mut self,
+        floor: u8,
+        direction: Direction,
+        expiry_seconds: u64,
+        now: Timestamp,
+    ) -> bool {
+        let node_id = self.node_id;
+        let Some(entry) = self.entry_mut(floor, direction) else {
+            return false;
+        };
+        if entry.state != HallCallState::None {
+            return false;
+        }
+
+        entry.call = Some(Call::new_with_expiration(floor, direction, expiry_seconds));
+        entry.requested_by = Some(node_id);
+        entry.assignee = None;
+        entry.acknowledged_by = BTreeSet::from([node_id]);
+        entry.transition(HallCallState::Unconfirmed, now);
+        true
+    }
+
+    // Merge entries received from a peer, returns whether the table changed
+    pub fn merge(&mut self, remote_entries: &[HallCallEntry]) -> bool {
+        let node_id = self.node_id;
+        let mut is_changed = false;
+
+        for remote in remote_entries {
+            let Some(local) = self.entry_mut(remote.floor, remote.direction) else {
+                continue;
+            };
+
+            // an initial entry carries no call, it must neither win nor block a real transition
+            if remote.is_initial {
+                continue;
+            }
+            if local.is_initial || remote.updated_at > local.updated_at {
+                *local = remote.clone();
+                if local.state == HallCallState::Unconfirmed {
+                    local.acknowledged_by.insert(node_id);
+                }
+                is_changed = true;
+            } else if remote.updated_at == local.updated_at
+                && local.state == HallCallState::Unconfirmed
+            {
+                let acknowledged = local.acknowledged_by.len();
+                local
+                    .acknowledged_by
+                    .extend(remote.acknowledged_by.iter().copied());
+                is_changed |= local.acknowledged_by.len() != acknowledged;
+            }
+        }
+        is_changed
+    }
+
+    // Confirm calls acknowledged by this node and every live peer, returns whether any changed
+    pub fn confirm(&mut self, live_peers: &[ID], now: Timestamp) -> bool {
+        let node_id = self.node_id;
+        let mut is_changed = false;
+
+        for entry in &mut self.entries {
+            let is_acknowledged = entry.acknowledged_by.contains(&node_id)
+                && live_peers
+                    .iter()
+                    .all(|peer| entry.acknowledged_by.contains(peer));
+            if entry.state == HallCallState::Unconfirmed && is_acknowledged {
+                entry.transition(HallCallState::Confirmed, now);
+                is_changed = true;
+            }
+        }
+        is_changed
+    }
+
+    // Take responsibility for a confirmed call, returns the call to serve
+    pub fn claim(&mut self, floor: u8, direction: Direction, now: Timestamp) -> Option<Call> {
+        let node_id = self.node_id;
+        let entry = self.entry_mut(floor, direction)?;
+        if entry.state != HallCallState::Confirmed {
+            return None;
+        }
+
+        entry.assignee = Some(node_id);
+        entry.transition(HallCallState::Serving, now);
+        entry.call.clone()
+    }
+
+    // Hand the calls served by the given node back for reassignment, returns the released calls
+    pub fn release(&mut self, assignee: &ID, now: Timestamp) -> Vec<Call> {
+        let mut released = Vec::new();
+
+        for entry in &mut self.entries {
+            if entry.state == HallCallState::Serving && entry.assignee.as_ref() == Some(assignee) {
+                entry.assignee = None;
+                entry.transition(HallCallState::Confirmed, now);
+                released.extend(entry.call.clone());
+            }
+        }
+        released
+    }
+
+    // Mark a call as served, returns whether the table changed
+    pub fn clear(&mut self, floor: u8, direction: Direction, now: Timestamp) -> bool {
+        let Some(entry) = self.entry_mut(floor, direction) else {
+            return false;
+        };
+        if entry.state == HallCallState::None {
+            return false;
+        }
+
+        entry.call = None;
+        entry.requested_by = None;
+        entry.assignee = None;
+        entry.acknowledged_by.clear();
+        entry.transition(HallCallState::None, now);
+        true
+    }
+
+    fn index(&self, floor: u8, direction: Direction) -> Option<usize> {
+        let index = floor as usize * 2
+            + match direction {
+                Direction::Up => 0,
+                Direction::Down => 1,
+            };
+        (index < self.entries.len()).then_some(index)
+    }
+
+    fn entry_mut(&mut self, floor: u8, direction: Direction) -> Option<&mut HallCallEntry> {
+        self.index(floor, direction)
+            .map(|index| &mut self.entries[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{current_timestamp, init_clock_with_random_id};
+
+    fn setup_test_clock() {
+        let _ = init_clock_with_random_id();
+    }
+
+    fn id(value: u8) -> ID {
+        ID::try_from(value).unwrap()
+    }
+
+    fn state(table: &HallCallTable, floor: u8, direction: Direction) -> HallCallState {
+        table.get(floor, direction).unwrap().state
+    }
+
+    #[test]
+    fn test_light_waits_for_every_peer() {
+        setup_test_clock();
+
+        let mut first = HallCallTable::new(4, id(1), current_timestamp());
+        let mut second = HallCallTable::new(4, id(2), current_timestamp());
+
+        assert!(first.request(2, Direction::Up, 60, current_timestamp()));
+        assert!(!first.confirm(&[id(2)], current_timestamp()));
+        assert!(!first.get(2, Direction::Up).unwrap().is_lit());
+
+        // the peer acknowledges by merging and sending its table back
+        assert!(second.merge(first.entries()));
+        assert_eq!(state(&second, 2, Direction::Up), HallCallState::Unconfirmed);
+        assert!(first.merge(second.entries()));
+
+        assert!(first.confirm(&[id(2)], current_timestamp()));
+        assert!(first.get(2, Direction::Up).unwrap().is_lit());
+
+        assert!(second.merge(first.entries()));
+        assert!(second.get(2, Direction::Up).unwrap().is_lit());
+    }
+
+    #[test]
+    fn test_alone_confirms_immediately() {
+        setup_test_clock();
+
+        let mut table = HallCallTable::new(4, id(1), current_timestamp());
+        table.request(0, Direction::Up, 60, current_timestamp());
+        assert!(table.confirm(&[], current_timestamp()));
+        assert_eq!(state(&table, 0, Direction::Up), HallCallState::Confirmed);
+    }
+
+    #[test]
+    fn test_newest_update_wins() {
+        setup_test_clock();
+
+        let mut first = HallCallTable::new(4, id(1), current_timestamp());
+        let mut second = HallCallTable::new(4, id(2), current_timestamp());
+
+        first.request(1, Direction::Down, 60, current_timestamp());
+        first.confirm(&[], current_timestamp());
+        second.merge(first.entries());
+
+        // the call gets served on the second node while the first holds a stale copy
+        second.clear(1, Direction::Down, current_timestamp());
+        let stale = first.clone();
+
+        assert!(first.merge(second.entries()));
+        assert_eq!(state(&first, 1, Direction::Down), HallCallState::None);
+        assert!(!second.merge(stale.entries()));
+        assert_eq!(state(&second, 1, Direction::Down), HallCallState::None);
+    }
+
+    #[test]
+    fn test_initial_entries_never_win() {
+        setup_test_clock();
+
+        let mut first = HallCallTable::new(4, id(1), current_timestamp());
+        first.request(2, Direction::Down, 60, current_timestamp());
+        first.confirm(&[], current_timestamp());
+
+        // a node joining later starts out with newer timestamps but no calls
+        let mut newcomer = HallCallTable::new(4, id(2), current_timestamp());
+        assert!(!first.merge(newcomer.entries()));
+        assert_eq!(state(&first, 2, Direction::Down), HallCallState::Confirmed);
+
+        assert!(newcomer.merge(first.entries()));
+        assert_eq!(
+            state(&newcomer, 2, Direction::Down),
+            HallCallState::Confirmed
+        );
+
+        // untouched entries stay as they are instead of flipping to the peer's copy
+        let mut other = HallCallTable::new(4, id(3), current_timestamp());
+        assert!(!other.merge(&first.entries()[..2]));
+        assert!(other.get(0, Direction::Up).unwrap().is_initial);
+    }
+
+    #[test]
+    fn test_claim_and_release() {
+        setup_test_clock();
+
+        let mut table = HallCallTable::new(4, id(1), current_timestamp());
+        assert!(table
+            .claim(3, Direction::Down, current_timestamp())
+            .is_none());
+
+        table.request(3, Direction::Down, 60, current_timestamp());
+        table.confirm(&[], current_timestamp());
+        let call = table
+            .claim(3, Direction::Down, current_timestamp())
+            .unwrap();
+        assert_eq!(call.target_floor, 3);
+        assert_eq!(state(&table, 3, Direction::Down), HallCallState::Serving);
+        assert_eq!(table.get(3, Direction::Down).unwrap().assignee, Some(id(1)));
+
+        assert_eq!(table.release(&id(1), current_timestamp()), vec![call]);
+        assert_eq!(state(&table, 3, Direction::Down), HallCallState::Confirmed);
+        assert!(table.get(3, Direction::Down).unwrap().is_lit());
+    }
+
+    #[test]
+    fn test_requests_out_of_range_are_ignored() {
+        setup_test_clock();
+
+        let mut table = HallCallTable::new(4, id(1), current_timestamp());
+        assert!(!table.request(4, Direction::Up, 60, current_timestamp()));
+        assert!(table.get(4, Direction::Up).is_none());
+    }
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use uhlc::{Timestamp, ID};
+
+use super::hall_calls::HallCallEntry;
+use crate::queue::assigner::ElevatorStatus;
+use crate::queue::Command;
+use crate::wire::{self, WireError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub node_id: ID,
+    pub timestamp: Timestamp,
+    pub is_available: bool,
+}
+
+// Messages exchanged between elevator nodes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Message {
+    Heartbeat(Heartbeat),
+    HallCalls {
+        node_id: ID,
+        entries: Vec<HallCallEntry>,
+    },
+    // Cab commands of the owner, sent by the owner as a backup or by a peer restoring them
+    CabOrders {
+        node_id: ID,
+        owner: ID,
+        commands: Vec<Command>,
+    },
+    // Ask the peers for their backup of the sender's cab commands
+    CabOrdersRequest {
+        node_id: ID,
+    },
+    // What the sender's elevator is doing, for assigning the hall calls
+    ElevatorStatus {
+        node_id: ID,
+        status: ElevatorStatus,
+    },
+}
+
+impl Message {
+    // Get the ID of the node that sent the message
+    pub fn sender(&self) -> &ID {
+        match self {
+            Message::Heartbeat(heartbeat) => &heartbeat.node_id,
+            Message::HallCalls { node_id, .. } => node_id,
+            Message::CabOrders { node_id, .. } => node_id,
+            Message::CabOrdersRequest { node_id } => node_id,
+            Message::ElevatorStatus { node_id, .. } => node_id,
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, WireError> {
+        wire::encode(self)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Message, WireError> {
+        wire::decode(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{current_timestamp, init_clock_with_random_id};
+    use crate::elevator::ElevatorState;
+    use crate::network::hall_calls::HallCallTable;
+    use crate::queue::{Call, Command, Direction};
+
+    #[test]
+    fn test_heartbeat_round_trip() {
+        let _ = init_clock_with_random_id();
+
+        let message = Message::Heartbeat(Heartbeat {
+            node_id: ID::try_from([0x01, 0x02, 0x03]).unwrap(),
+            timestamp: current_timestamp(),
+            is_available: false,
+        });
+
+        let bytes = message.encode().unwrap();
+        assert_eq!(Message::decode(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn test_hall_calls_round_trip() {
+        let _ = init_clock_with_random_id();
+
+        let node_id = ID::try_from([0x04]).unwrap();
+        let mut table = HallCallTable::new(4, node_id, current_timestamp());
+        table.request(1, Direction::Up, 60, current_timestamp());
+
+        let message = Message::HallCalls {
+            node_id,
+            entries: table.entries().to_vec(),
+        };
+        let bytes = message.encode().unwrap();
+        let decoded = Message::decode(&bytes).unwrap();
+        assert_eq!(decoded.sender(), &node_id);
+        assert_eq!(decoded, message);
+    }
+
+    #[test]
+    fn test_cab_orders_round_trip() {
+        let _ = init_clock_with_random_id();
+
+        let owner = ID::try_from([0x05]).unwrap();
+        let peer = ID::try_from([0x06]).unwrap();
+        let message = Message::CabOrders {
+            node_id: peer,
+            owner,
+            commands: vec![Command::new(2), Command::new(0)],
+        };
+        let bytes = message.encode().unwrap();
+        let decoded = Message::decode(&bytes).unwrap();
+        assert_eq!(decoded.sender(), &peer);
+        assert_eq!(decoded, message);
+    }
+
+    #[test]
+    fn test_elevator_status_round_trip() {
+        let _ = init_clock_with_random_id();
+
+        let message = Message::ElevatorStatus {
+            node_id: ID::try_from([0x07]).unwrap(),
+            status: ElevatorStatus {
+                floor: 2,
+                direction: Some(Direction::Down),
+                behaviour: ElevatorState::Moving,
+                commands: vec![Command::new(0)],
+                calls: vec![Call::new(1, Direction::Down)],
+            },
+        };
+        let bytes = message.encode().unwrap();
+        assert_eq!(Message::decode(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn test_decode_garbage_fails() {
+        assert!(Message::decode(&[0xff, 0xff, 0xff]).is_err());
+    }
+}
@@ -0,0 +1,11 @@
+pub mod coordinator;
+pub mod hall_calls;
+pub mod message;
+pub mod node;
+pub mod peers;
+
+pub use coordinator::Coordinator;
+pub use hall_calls::{HallCallEntry, HallCallState, HallCallTable};
+pub use message::{Heartbeat, Message};
+pub use node::NetworkNode;
+pub use peers::{Peer, PeerEvent, PeerTable};
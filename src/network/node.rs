@@ -0,0 +1,281 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel as channel;
+use log::{debug, error, info, warn};
+use socket2::{Domain, Protocol, Socket, Type};
+use uhlc::ID;
+
+use super::message::{Heartbeat, Message};
+use super::peers::{Peer, PeerEvent, PeerTable};
+use crate::clock::{current_timestamp, update_clock_with_timestamp};
+use crate::config::NetworkConfig;
+
+const MAX_DATAGRAM_SIZE: usize = 65507;
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Network endpoint for one elevator node
+// Broadcasts heartbeats on the configured address and keeps track of which peers are alive.
+// Every other message is broadcast from the outbound channel and delivered on the inbound one.
+pub struct NetworkNode {
+    node_id: ID,
+    socket: UdpSocket,
+    broadcast_address: SocketAddr,
+    heartbeat_interval: Duration,
+    peers: PeerTable,
+    is_available: bool,
+    availability_rx: channel::Receiver<bool>,
+    peer_event_tx: channel::Sender<PeerEvent>,
+    outbound_rx: channel::Receiver<Message>,
+    inbound_tx: channel::Sender<Message>,
+    terminate_rx: channel::Receiver<()>,
+}
+
+impl NetworkNode {
+    pub fn new(
+        config: &NetworkConfig,
+        node_id: ID,
+        availability_rx: channel::Receiver<bool>,
+        peer_event_tx: channel::Sender<PeerEvent>,
+        outbound_rx: channel::Receiver<Message>,
+        inbound_tx: channel::Sender<Message>,
+        terminate_rx: channel::Receiver<()>,
+    ) -> Result<NetworkNode, io::Error> {
+        let port = u16::try_from(config.port)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Invalid network port"))?;
+        let broadcast_address = (config.address.as_str(), port)
+            .to_socket_addrs()?
+            .find(SocketAddr::is_ipv4)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "No IPv4 broadcast address")
+            })?;
+
+        Ok(NetworkNode {
+            node_id,
+            socket: bind_broadcast_socket(port)?,
+            broadcast_address,
+            heartbeat_interval: Duration::from_millis(config.heartbeat_interval_milliseconds),
+            peers: PeerTable::new(Duration::from_millis(config.peer_timeout_milliseconds)),
+            is_available: true,
+            availability_rx,
+            peer_event_tx,
+            outbound_rx,
+            inbound_tx,
+            terminate_rx,
+        })
+    }
+
+    pub fn run(mut self) {
+        info!(
+            "Starting network node {} broadcasting on {}",
+            self.node_id, self.broadcast_address
+        );
+
+        let mut buffer = vec![0u8; MAX_DATAGRAM_SIZE];
+        let mut next_heartbeat = Instant::now();
+
+        while let Err(channel::TryRecvError::Empty) = self.terminate_rx.try_recv() {
+            for is_available in self.availability_rx.try_iter() {
+                self.is_available = is_available;
+                // let peers know right away instead of waiting for the next heartbeat
+                next_heartbeat = Instant::now();
+            }
+
+            for message in self.outbound_rx.try_iter() {
+                self.broadcast(&message);
+            }
+
+            let now = Instant::now();
+            if now >= next_heartbeat {
+                self.broadcast_heartbeat();
+                next_heartbeat = now + self.heartbeat_interval;
+            }
+
+            for event in self.peers.remove_lost_peers(now) {
+                info!("{:?}", event);
+                let _ = self.peer_event_tx.send(event);
+            }
+
+            let timeout = next_heartbeat
+                .saturating_duration_since(Instant::now())
+                .clamp(Duration::from_millis(1), POLL_INTERVAL);
+            if let Err(error) = self.socket.set_read_timeout(Some(timeout)) {
+                error!("Failed to set socket read timeout {}", error);
+            }
+
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, _)) => self.on_datagram(&buffer[..length]),
+                Err(error)
+                    if error.kind() == io::ErrorKind::WouldBlock
+                        || error.kind() == io::ErrorKind::TimedOut => {}
+                Err(error) => error!("Failed to receive datagram {}", error),
+            }
+        }
+    }
+
+    fn broadcast_heartbeat(&self) {
+        self.broadcast(&Message::Heartbeat(Heartbeat {
+            node_id: self.node_id,
+            timestamp: current_timestamp(),
+            is_available: self.is_available,
+        }));
+    }
+
+    fn broadcast(&self, message: &Message) {
+        match message.encode() {
+            Ok(bytes) => {
+                if let Err(error) = self.socket.send_to(&bytes, self.broadcast_address) {
+                    warn!("Failed to broadcast message {}", error);
+                }
+            }
+            Err(error) => error!("Failed to encode message {}", error),
+        }
+    }
+
+    fn on_datagram(&mut self, bytes: &[u8]) {
+        let message = match Message::decode(bytes) {
+            Ok(message) => message,
+            Err(error) => {
+                debug!("Dropping malformed datagram {}", error);
+                return;
+            }
+        };
+
+        // broadcasts loop back to the sender
+        if *message.sender() == self.node_id {
+            return;
+        }
+
+        match message {
+            Message::Heartbeat(heartbeat) => self.on_heartbeat(heartbeat),
+            message => {
+                let _ = self.inbound_tx.send(message);
+            }
+        }
+    }
+
+    fn on_heartbeat(&mut self, heartbeat: Heartbeat) {
+        if let Err(error) = update_clock_with_timestamp(&heartbeat.timestamp) {
+            warn!(
+                "Rejected clock update from {}: {}",
+                heartbeat.node_id, error
+            );
+        }
+
+        let peer = Peer {
+            node_id: heartbeat.node_id,
+            is_available: heartbeat.is_available,
+            last_timestamp: heartbeat.timestamp,
+        };
+        if let Some(event) = self.peers.update(peer, Instant::now()) {
+            info!("{:?}", event);
+            let _ = self.peer_event_tx.send(event);
+        }
+    }
+}
+
+// Bind a broadcast socket that several nodes on the same machine can share
+fn bind_broadcast_socket(port: u16) -> Result<UdpSocket, io::Error> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_broadcast(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into())?;
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::init_clock_with_random_id;
+    use std::thread;
+
+    struct TestNode {
+        peer_events: channel::Receiver<PeerEvent>,
+        availability: channel::Sender<bool>,
+        outbound: channel::Sender<Message>,
+        inbound: channel::Receiver<Message>,
+        terminate: channel::Sender<()>,
+    }
+
+    // A port nothing else is bound to, so parallel test runs do not hear each other
+    fn free_port() -> u32 {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        socket.local_addr().unwrap().port().into()
+    }
+
+    fn spawn_node(node_id: ID, port: u32) -> TestNode {
+        let config = NetworkConfig {
+            address: "127.255.255.255".to_string(),
+            port,
+            heartbeat_interval_milliseconds: 20,
+            peer_timeout_milliseconds: 200,
+        };
+        let (availability_tx, availability_rx) = channel::unbounded();
+        let (peer_event_tx, peer_event_rx) = channel::unbounded();
+        let (outbound_tx, outbound_rx) = channel::unbounded();
+        let (inbound_tx, inbound_rx) = channel::unbounded();
+        let (terminate_tx, terminate_rx) = channel::unbounded();
+
+        let node = NetworkNode::new(
+            &config,
+            node_id,
+            availability_rx,
+            peer_event_tx,
+            outbound_rx,
+            inbound_tx,
+            terminate_rx,
+        )
+        .unwrap();
+        thread::spawn(move || node.run());
+
+        TestNode {
+            peer_events: peer_event_rx,
+            availability: availability_tx,
+            outbound: outbound_tx,
+            inbound: inbound_rx,
+            terminate: terminate_tx,
+        }
+    }
+
+    #[test]
+    fn test_nodes_discover_and_lose_each_other() {
+        let _ = init_clock_with_random_id();
+
+        let first_id = ID::try_from(0x0au8).unwrap();
+        let second_id = ID::try_from(0x0bu8).unwrap();
+        let port = free_port();
+        let first = spawn_node(first_id, port);
+        let second = spawn_node(second_id, port);
+
+        let timeout = Duration::from_secs(2);
+        match first.peer_events.recv_timeout(timeout).unwrap() {
+            PeerEvent::PeerJoined(peer) => assert_eq!(peer.node_id, second_id),
+            event => panic!("Unexpected event {:?}", event),
+        }
+
+        // messages reach the peer but are not looped back to the sender
+        let message = Message::HallCalls {
+            node_id: first_id,
+            entries: Vec::new(),
+        };
+        first.outbound.send(message.clone()).unwrap();
+        assert_eq!(second.inbound.recv_timeout(timeout).unwrap(), message);
+        assert!(first
+            .inbound
+            .recv_timeout(Duration::from_millis(100))
+            .is_err());
+
+        second.availability.send(false).unwrap();
+        match first.peer_events.recv_timeout(timeout).unwrap() {
+            PeerEvent::AvailabilityChanged(peer) => assert!(!peer.is_available),
+            event => panic!("Unexpected event {:?}", event),
+        }
+
+        second.terminate.send(()).unwrap();
+        assert_eq!(
+            first.peer_events.recv_timeout(timeout).unwrap(),
+            PeerEvent::PeerLost(second_id)
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use uhlc::{Timestamp, ID};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peer {
+    pub node_id: ID,
+    pub is_available: bool,
+    pub last_timestamp: Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerEvent {
+    PeerJoined(Peer),
+    PeerLost(ID),
+    AvailabilityChanged(Peer),
+}
+
+#[derive(Debug)]
+struct PeerEntry {
+    peer: Peer,
+    last_seen: Instant,
+}
+
+// Table of peers that have been heard from within the lost-peer timeout
+#[derive(Debug)]
+pub struct PeerTable {
+    timeout: Duration,
+    peers: HashMap<ID, PeerEntry>,
+}
+
+impl PeerTable {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            peers: HashMap::new(),
+        }
+    }
+
+    // Record a heartbeat from a peer, returns an event if the peer is new or changed availability
+    pub fn update(&mut self, peer: Peer, now: Instant) -> Option<PeerEvent> {
+        match self.peers.get_mut(&peer.node_id) {
+            Some(entry) => {
+                // heartbeats can be reordered, so only newer ones refresh the peer state
+                if peer.last_timestamp <= entry.peer.last_timestamp {
+                    return None;
+                }
+                let was_available = entry.peer.is_available;
+                entry.peer = peer;
+                entry.last_seen = now;
+
+                if was_available != peer.is_available {
+                    Some(PeerEvent::AvailabilityChanged(peer))
+                } else {
+                    None
+                }
+            }
+            None => {
+                self.peers.insert(
+                    peer.node_id,
+                    PeerEntry {
+                        peer,
+                        last_seen: now,
+                    },
+                );
+                Some(PeerEvent::PeerJoined(peer))
+            }
+        }
+    }
+
+    // Remove peers that have been silent for longer than the timeout
+    pub fn remove_lost_peers(&mut self, now: Instant) -> Vec<PeerEvent> {
+        let lost: Vec<ID> = self
+            .peers
+            .iter()
+            .filter(|(_, entry)| now.duration_since(entry.last_seen) > self.timeout)
+            .map(|(node_id, _)| *node_id)
+            .collect();
+
+        lost.into_iter()
+            .map(|node_id| {
+                self.peers.remove(&node_id);
+                PeerEvent::PeerLost(node_id)
+            })
+            .collect()
+    }
+
+    pub fn get(&self, node_id: &ID) -> Option<&Peer> {
+        self.peers.get(node_id).map(|entry| &entry.peer)
+    }
+
+    // Get all live peers
+    pub fn peers(&self) -> Vec<Peer> {
+        self.peers.values().map(|entry| entry.peer).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{current_timestamp, init_clock_with_random_id};
+
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    fn setup_test_clock() {
+        let _ = init_clock_with_random_id();
+    }
+
+    fn peer(node_id: u8, is_available: bool) -> Peer {
+        Peer {
+            node_id: ID::try_from(node_id).unwrap(),
+            is_available,
+            last_timestamp: current_timestamp(),
+        }
+    }
+
+    #[test]
+    fn test_peer_joined_once() {
+        setup_test_clock();
+
+        let now = Instant::now();
+        let mut table = PeerTable::new(TIMEOUT);
+
+        let first = peer(1, true);
+        assert_eq!(table.update(first, now), Some(PeerEvent::PeerJoined(first)));
+        assert_eq!(table.update(peer(1, true), now), None);
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_peer_lost_after_timeout() {
+        setup_test_clock();
+
+        let start = Instant::now();
+        let mut table = PeerTable::new(TIMEOUT);
+
+        table.update(peer(1, true), start);
+        table.update(peer(2, true), start + Duration::from_millis(400));
+
+        let events = table.remove_lost_peers(start + Duration::from_millis(600));
+        assert_eq!(
+            events,
+            vec![PeerEvent::PeerLost(ID::try_from(1u8).unwrap())]
+        );
+        assert_eq!(table.len(), 1);
+        assert!(table.get(&ID::try_from(2u8).unwrap()).is_some());
+    }
+
+    #[test]
+    fn test_peer_availability_changes() {
+        setup_test_clock();
+
+        let now = Instant::now();
+        let mut table = PeerTable::new(TIMEOUT);
+
+        table.update(peer(1, true), now);
+        let halted = peer(1, false);
+        assert_eq!(
+            table.update(halted, now),
+            Some(PeerEvent::AvailabilityChanged(halted))
+        );
+        assert!(!table.get(&halted.node_id).unwrap().is_available);
+    }
+
+    #[test]
+    fn test_stale_heartbeat_is_ignored() {
+        setup_test_clock();
+
+        let now = Instant::now();
+        let mut table = PeerTable::new(TIMEOUT);
+
+        let stale = peer(1, false);
+        let fresh = peer(1, true);
+        table.update(fresh, now);
+        assert_eq!(table.update(stale, now + TIMEOUT), None);
+        assert!(table.get(&fresh.node_id).unwrap().is_available);
+
+        // a stale heartbeat does not keep the peer alive either
+        assert_eq!(table.remove_lost_peers(now + TIMEOUT * 2).len(), 1);
+    }
+}
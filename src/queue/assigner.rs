@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::order::{Call, Command, Direction};
+use crate::elevator::ElevatorState;
+
+// Time to travel between two adjacent floors, as measured on the lab elevators
+pub const ESTIMATED_TRAVEL_TIME: Duration = Duration::from_secs(2);
+
+// What an elevator is doing and has left to do, shared with the peers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElevatorStatus {
+    pub floor: u8, // last floor reached
+    pub direction: Option<Direction>,
+    pub behaviour: ElevatorState,
+    pub commands: Vec<Command>,
+    pub calls: Vec<Call>, // hall calls already handed to the elevator
+}
+
+// Assigns hall calls across the cluster by the time each elevator needs to become idle
+// Every call is given to the elevator that would finish all of its work the soonest with the
+// call added, so a call goes to a busy elevator only if it passes by anyway. The outcome only
+// depends on the input, so nodes with the same view of the cluster agree on it.
+#[derive(Debug, Clone)]
+pub struct HallCallAssigner {
+    num_floors: u8,
+    travel_time: Duration,
+    door_open_duration: Duration,
+}
+
+impl HallCallAssigner {
+    pub fn new(num_floors: u8, travel_time: Duration, door_open_duration: Duration) -> Self {
+        Self {
+            num_floors,
+            travel_time,
+            door_open_duration,
+        }
+    }
+
+    pub fn travel_time(&self) -> Duration {
+        self.travel_time
+    }
+
+    // Split the unassigned calls between the elevators, ties go to the lowest key
+    pub fn assign<K: Copy + Ord>(
+        &self,
+        elevators: &BTreeMap<K, ElevatorStatus>,
+        calls: &[Call],
+    ) -> BTreeMap<K, Vec<Call>> {
+        let mut assignment: BTreeMap<K, Vec<Call>> =
+            elevators.keys().map(|key| (*key, Vec::new())).collect();
+
+        // oldest first, the order decides which call gets the better placed elevator
+        let mut calls: Vec<&Call> = calls.iter().collect();
+        calls.sort_by_key(|call| (call.created_at, call.id));
+
+        for call in calls {
+            let best = elevators.iter().min_by_key(|(key, status)| {
+                let mut assigned = assignment[*key].clone();
+                assigned.push(call.clone());
+                self.time_to_idle(status, &assigned)
+            });
+            if let Some((key, _)) = best {
+                if let Some(assigned) = assignment.get_mut(key) {
+                    assigned.push(call.clone());
+                }
+            }
+        }
+        assignment
+    }
+
+    // Simulate the elevator serving its orders plus the extra calls, returns how long it takes
+    pub fn time_to_idle(&self, status: &ElevatorStatus, extra_calls: &[Call]) -> Duration {
+        let mut simulation = Simulation::new(self.num_floors, status, extra_calls);
+        let mut direction = status.direction;
+        let mut duration = Duration::ZERO;
+
+        match (status.behaviour, direction) {
+            // on average halfway to the next floor
+            (ElevatorState::Moving, Some(moving)) => {
+                duration += self.travel_time / 2;
+                simulation.step(moving);
+            }
+            // on average halfway through the door cycle, which serves the current floor
+            (ElevatorState::DoorOpen, _) => {
+                duration += self.door_open_duration / 2;
+                simulation.clear_at_floor(direction);
+                direction = simulation.choose_direction(direction);
+                if direction.is_none() {
+                    return duration;
+                }
+            }
+            _ => {}
+        }
+
+        loop {
+            if simulation.should_stop(direction) {
+                if simulation.clear_at_floor(direction) {
+                    duration += self.door_open_duration;
+                }
+                direction = simulation.choose_direction(direction);
+            }
+            let Some(moving) = direction else {
+                return duration;
+            };
+            if !simulation.step(moving) {
+                // nothing left in range, the requests must be out of it
+                return duration;
+            }
+            duration += self.travel_time;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct FloorRequests {
+    up: bool,
+    down: bool,
+    cab: bool,
+}
+
+impl FloorRequests {
+    fn any(&self) -> bool {
+        self.up || self.down || self.cab
+    }
+}
+
+// Requests of a single elevator as it works through them floor by floor
+#[derive(Debug)]
+struct Simulation {
+    floor: usize,
+    requests: Vec<FloorRequests>,
+}
+
+impl Simulation {
+    fn new(num_floors: u8, status: &ElevatorStatus, extra_calls: &[Call]) -> Self {
+        let mut requests = vec![FloorRequests::default(); num_floors as usize];
+        for command in &status.commands {
+            if let Some(floor) = requests.get_mut(command.target_floor as usize) {
+                floor.cab = true;
+            }
+        }
+        for call in status.calls.iter().chain(extra_calls) {
+            if let Some(floor) = requests.get_mut(call.target_floor as usize) {
+                match call.direction {
+                    Direction::Up => floor.up = true,
+                    Direction::Down => floor.down = true,
+                }
+            }
+        }
+
+        Self {
+            floor: (status.floor as usize).min(requests.len().saturating_sub(1)),
+            requests,
+        }
+    }
+
+    fn is_any_above(&self) -> bool {
+        self.requests[self.floor + 1..]
+            .iter()
+            .any(FloorRequests::any)
+    }
+
+    fn is_any_below(&self) -> bool {
+        self.requests[..self.floor].iter().any(FloorRequests::any)
+    }
+
+    // Keep going while there is something ahead, otherwise turn around or stop
+    fn choose_direction(&self, direction: Option<Direction>) -> Option<Direction> {
+        if direction == Some(Direction::Down) && self.is_any_below() {
+            Some(Direction::Down)
+        } else if self.is_any_above() {
+            Some(Direction::Up)
+        } else if self.is_any_below() {
+            Some(Direction::Down)
+        } else {
+            None
+        }
+    }
+
+    fn should_stop(&self, direction: Option<Direction>) -> bool {
+        let here = self.requests[self.floor];
+        match direction {
+            Some(Direction::Up) => here.cab || here.up || !self.is_any_above(),
+            Some(Direction::Down) => here.cab || here.down || !self.is_any_below(),
+            None => true,
+        }
+    }
+
+    // Serve the current floor, a call in the other direction is only taken when turning around
+    // Returns whether anything was served, that is whether the door opens
+    fn clear_at_floor(&mut self, direction: Option<Direction>) -> bool {
+        let is_any_above = self.is_any_above();
+        let is_any_below = self.is_any_below();
+        let here = &mut self.requests[self.floor];
+        let before = *here;
+
+        here.cab = false;
+        match direction {
+            Some(Direction::Up) => {
+                if !here.up && !is_any_above {
+                    here.down = false;
+                }
+                here.up = false;
+            }
+            Some(Direction::Down) => {
+                if !here.down && !is_any_below {
+                    here.up = false;
+                }
+                here.down = false;
+            }
+            None => {
+                here.up = false;
+                here.down = false;
+            }
+        }
+        *here != before
+    }
+
+    // Move one floor, returns false at the end of the shaft
+    fn step(&mut self, direction: Direction) -> bool {
+        let next = match direction {
+            Direction::Up => self.floor + 1,
+            Direction::Down => match self.floor.checked_sub(1) {
+                Some(next) => next,
+                None => return false,
+            },
+        };
+        if next >= self.requests.len() {
+            return false;
+        }
+        self.floor = next;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::init_clock_with_random_id;
+
+    fn assigner() -> HallCallAssigner {
+        HallCallAssigner::new(4, Duration::from_secs(2), Duration::from_secs(3))
+    }
+
+    fn status(floor: u8, direction: Option<Direction>, behaviour: ElevatorState) -> ElevatorStatus {
+        ElevatorStatus {
+            floor,
+            direction,
+            behaviour,
+            commands: Vec::new(),
+            calls: Vec::new(),
+        }
+    }
+
+    fn idle(floor: u8) -> ElevatorStatus {
+        status(floor, None, ElevatorState::Idle)
+    }
+
+    fn floors(calls: &[Call]) -> Vec<(u8, Direction)> {
+        calls
+            .iter()
+            .map(|call| (call.target_floor, call.direction))
+            .collect()
+    }
+
+    #[test]
+    fn test_time_to_idle() {
+        let _ = init_clock_with_random_id();
+        let assigner = assigner();
+
+        assert_eq!(assigner.time_to_idle(&idle(1), &[]), Duration::ZERO);
+
+        // two floors up and one door cycle
+        let mut cab = idle(0);
+        cab.commands.push(Command::new(2));
+        assert_eq!(assigner.time_to_idle(&cab, &[]), Duration::from_secs(7));
+
+        // a call at the floor of an open door is served by the rest of its cycle
+        let door_open = status(2, Some(Direction::Up), ElevatorState::DoorOpen);
+        assert_eq!(
+            assigner.time_to_idle(&door_open, &[Call::new(2, Direction::Up)]),
+            Duration::from_millis(1500)
+        );
+
+        // half a floor to go, stop on the way up for the call, then the cab order at the top
+        let mut moving = status(1, Some(Direction::Up), ElevatorState::Moving);
+        moving.commands.push(Command::new(3));
+        assert_eq!(
+            assigner.time_to_idle(&moving, &[Call::new(2, Direction::Up)]),
+            Duration::from_secs(9)
+        );
+    }
+
+    #[test]
+    fn test_calls_go_to_the_elevator_done_first() {
+        let _ = init_clock_with_random_id();
+
+        let mut busy = status(0, Some(Direction::Up), ElevatorState::Moving);
+        busy.commands.push(Command::new(3));
+        let elevators = BTreeMap::from([(1, busy), (2, idle(3))]);
+
+        let assignment = assigner().assign(&elevators, &[Call::new(1, Direction::Down)]);
+        assert!(assignment[&1].is_empty());
+        assert_eq!(floors(&assignment[&2]), vec![(1, Direction::Down)]);
+    }
+
+    #[test]
+    fn test_calls_are_spread_over_idle_elevators() {
+        let _ = init_clock_with_random_id();
+
+        let elevators = BTreeMap::from([(1, idle(0)), (2, idle(3))]);
+        let calls = [Call::new(3, Direction::Down), Call::new(0, Direction::Up)];
+        let assignment = assigner().assign(&elevators, &calls);
+        assert_eq!(floors(&assignment[&1]), vec![(0, Direction::Up)]);
+        assert_eq!(floors(&assignment[&2]), vec![(3, Direction::Down)]);
+
+        // identical elevators, the lowest key wins
+        let elevators = BTreeMap::from([(7, idle(1)), (4, idle(1))]);
+        let assignment = assigner().assign(&elevators, &[Call::new(2, Direction::Up)]);
+        assert_eq!(assignment[&4].len(), 1);
+        assert!(assignment[&7].is_empty());
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::order::Command;
+use crate::wire::{self, WireError};
+
+// Size of the length prefix in front of every record
+const LENGTH_BYTES: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalEntry {
+    CommandAdded(Command),
+    OrderRemoved(Uuid),
+}
+
+// Write-ahead journal for cab commands
+// Every change is appended as a length prefixed record and synced to disk before the caller
+// continues, so a crash never loses an accepted command. A record cut short by a crash is
+// dropped on replay, while a complete record that fails to decode stops the open and leaves the
+// file untouched. Once enough records pile up the journal is rewritten with only the live
+// commands, through a temporary file that atomically replaces the old journal.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    compaction_threshold: usize,
+    records: usize, // records written since the last compaction
+}
+
+impl Journal {
+    // Open the journal at the given path, returns it along with the commands it holds
+    pub fn open(
+        path: impl AsRef<Path>,
+        compaction_threshold: usize,
+    ) -> Result<(Journal, Vec<Command>), JournalError> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let (entries, valid_length) = read_entries(&bytes)?;
+        if valid_length < bytes.len() {
+            warn!(
+                "Dropping {} bytes of incomplete records from journal {}",
+                bytes.len() - valid_length,
+                path.display()
+            );
+            file.set_len(valid_length as u64)?;
+            file.sync_all()?;
+        }
+
+        let records = entries.len();
+        let commands = replay(entries);
+        let mut journal = Journal {
+            path,
+            file,
+            compaction_threshold,
+            records,
+        };
+        if journal.needs_compaction() {
+            journal.compact(&commands)?;
+        }
+        Ok((journal, commands))
+    }
+
+    pub fn record_command_added(&mut self, command: &Command) -> Result<(), JournalError> {
+        self.append(&JournalEntry::CommandAdded(command.clone()))
+    }
+
+    pub fn record_order_removed(&mut self, order_id: Uuid) -> Result<(), JournalError> {
+        self.append(&JournalEntry::OrderRemoved(order_id))
+    }
+
+    pub fn needs_compaction(&self) -> bool {
+        self.records >= self.compaction_threshold
+    }
+
+    // Replace the journal with one that only holds the given commands
+    pub fn compact(&mut self, commands: &[Command]) -> Result<(), JournalError> {
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".tmp");
+        let temporary_path = PathBuf::from(temporary_path);
+
+        let mut temporary = File::create(&temporary_path)?;
+        for command in commands {
+            let record = encode_record(&JournalEntry::CommandAdded(command.clone()))?;
+            temporary.write_all(&record)?;
+        }
+        temporary.sync_all()?;
+        drop(temporary);
+
+        fs::rename(&temporary_path, &self.path)?;
+        sync_parent_directory(&self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.records = commands.len();
+        Ok(())
+    }
+
+    fn append(&mut self, entry: &JournalEntry) -> Result<(), JournalError> {
+        let record = encode_record(entry)?;
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        self.records += 1;
+        Ok(())
+    }
+}
+
+fn encode_record(entry: &JournalEntry) -> Result<Vec<u8>, JournalError> {
+    let payload = wire::encode(entry)?;
+    let mut record = Vec::with_capacity(LENGTH_BYTES + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&payload);
+    Ok(record)
+}
+
+// Decode records until the end or a torn last record, returns them and the valid length
+// Only a record running past the end of the file counts as torn, any other damage is an error.
+fn read_entries(bytes: &[u8]) -> Result<(Vec<JournalEntry>, usize), WireError> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+
+    while let Some(header) = bytes.get(offset..offset + LENGTH_BYTES) {
+        let length = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+        let start = offset + LENGTH_BYTES;
+        let Some(payload) = bytes.get(start..start + length) else {
+            break;
+        };
+        entries.push(wire::decode(payload)?);
+        offset = start + length;
+    }
+    Ok((entries, offset))
+}
+
+fn replay(entries: Vec<JournalEntry>) -> Vec<Command> {
+    let mut commands: Vec<Command> = Vec::new();
+    for entry in entries {
+        match entry {
+            JournalEntry::CommandAdded(command) => {
+                if !commands.iter().any(|active| active.id == command.id) {
+                    commands.push(command);
+                }
+            }
+            JournalEntry::OrderRemoved(order_id) => {
+                commands.retain(|command| command.id != order_id);
+            }
+        }
+    }
+    commands
+}
+
+// The rename is only durable once the directory entry itself has been synced
+fn sync_parent_directory(path: &Path) -> io::Result<()> {
+    match path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        Some(parent) => File::open(parent)?.sync_all(),
+        None => File::open(".")?.sync_all(),
+    }
+}
+
+// Errors that can occur when reading or writing the journal
+#[derive(Debug)]
+pub enum JournalError {
+    Io(io::Error),
+    Encoding(WireError),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::Io(error) => write!(f, "Journal I/O error: {}", error),
+            JournalError::Encoding(error) => write!(f, "Journal encoding error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+impl From<io::Error> for JournalError {
+    fn from(error: io::Error) -> Self {
+        JournalError::Io(error)
+    }
+}
+
+impl From<WireError> for JournalError {
+    fn from(error: WireError) -> Self {
+        JournalError::Encoding(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::init_clock_with_random_id;
+
+    fn setup_test_clock() {
+        let _ = init_clock_with_random_id();
+    }
+
+    fn journal_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("elevators-{}", Uuid::new_v4()))
+            .join("cab_orders.journal")
+    }
+
+    #[test]
+    fn test_replay_restores_live_commands() {
+        setup_test_clock();
+        let path = journal_path();
+
+        let first = Command::new(1);
+        let second = Command::new(3);
+        {
+            let (mut journal, commands) = Journal::open(&path, 100).unwrap();
+            assert!(commands.is_empty());
+            journal.record_command_added(&first).unwrap();
+            journal.record_command_added(&second).unwrap();
+            journal.record_order_removed(first.id).unwrap();
+        }
+
+        let (_, commands) = Journal::open(&path, 100).unwrap();
+        assert_eq!(commands, vec![second]);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_torn_record_is_dropped() {
+        setup_test_clock();
+        let path = journal_path();
+
+        let command = Command::new(2);
+        {
+            let (mut journal, _) = Journal::open(&path, 100).unwrap();
+            journal.record_command_added(&command).unwrap();
+        }
+
+        // simulate a crash halfway through writing the next record
+        let record = encode_record(&JournalEntry::CommandAdded(Command::new(0))).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&record[..record.len() / 2]).unwrap();
+        drop(file);
+
+        let (mut journal, commands) = Journal::open(&path, 100).unwrap();
+        assert_eq!(commands, vec![command.clone()]);
+
+        // appending after the repair must not be hidden behind the torn record
+        journal.record_order_removed(command.id).unwrap();
+        let (_, commands) = Journal::open(&path, 100).unwrap();
+        assert!(commands.is_empty());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_undecodable_record_keeps_the_journal() {
+        setup_test_clock();
+        let path = journal_path();
+
+        {
+            let (mut journal, _) = Journal::open(&path, 100).unwrap();
+            journal.record_command_added(&Command::new(1)).unwrap();
+        }
+
+        // a complete record from a newer format, followed by one that is still readable
+        let mut record = encode_record(&JournalEntry::CommandAdded(Command::new(2))).unwrap();
+        record[LENGTH_BYTES] = wire::WIRE_VERSION + 1;
+        record.extend(encode_record(&JournalEntry::CommandAdded(Command::new(3))).unwrap());
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&record).unwrap();
+        drop(file);
+
+        let size = fs::metadata(&path).unwrap().len();
+        assert!(matches!(
+            Journal::open(&path, 100),
+            Err(JournalError::Encoding(WireError::UnsupportedVersion(_)))
+        ));
+        assert_eq!(fs::metadata(&path).unwrap().len(), size);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_compaction_keeps_only_live_commands() {
+        setup_test_clock();
+        let path = journal_path();
+
+        let kept = Command::new(3);
+        let (mut journal, _) = Journal::open(&path, 4).unwrap();
+        for floor in 0..3 {
+            let command = Command::new(floor);
+            journal.record_command_added(&command).unwrap();
+            journal.record_order_removed(command.id).unwrap();
+        }
+        journal.record_command_added(&kept).unwrap();
+        assert!(journal.needs_compaction());
+
+        let size = fs::metadata(&path).unwrap().len();
+        journal.compact(std::slice::from_ref(&kept)).unwrap();
+        assert!(!journal.needs_compaction());
+        assert!(fs::metadata(&path).unwrap().len() < size);
+
+        let (_, commands) = Journal::open(&path, 4).unwrap();
+        assert_eq!(commands, vec![kept]);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
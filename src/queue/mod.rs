@@ -1,6 +1,10 @@
+pub mod assigner;
+pub mod journal;
 pub mod order;
+#[allow(clippy::module_inception)]
 pub mod queue;
 pub mod scheduler;
 
+pub use journal::{Journal, JournalError};
 pub use order::{Call, Command, Direction, Expiration, Order};
 pub use queue::{OrderQueue, QueueError};
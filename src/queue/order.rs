@@ -1,17 +1,18 @@
 use crate::clock::{current_timestamp, TimestampExt};
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use uhlc::Timestamp;
 use uuid::Uuid;
 
 const ORDER_EXPIRY_SECONDS: u64 = 60;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Call {
     pub id: Uuid,
     pub target_floor: u8,
@@ -54,7 +55,7 @@ impl Call {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Command {
     pub id: Uuid,
     pub target_floor: u8,
@@ -129,7 +130,7 @@ impl Expiration for Command {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Order {
     Call(Call),
     Command(Command),
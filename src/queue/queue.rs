@@ -1,6 +1,9 @@
+use log::error;
 use std::collections::VecDeque;
+use std::path::Path;
 use uuid::Uuid;
 
+use super::journal::{Journal, JournalError};
 use super::order::{Call, Command, Expiration, Order};
 use super::scheduler::{Scheduler, SchedulerContext};
 
@@ -9,6 +12,7 @@ use super::scheduler::{Scheduler, SchedulerContext};
 pub struct OrderQueue {
     orders: VecDeque<Order>,
     max_size: Option<usize>,
+    journal: Option<Journal>, // persists cab commands across restarts
 }
 
 impl OrderQueue {
@@ -17,6 +21,7 @@ impl OrderQueue {
         Self {
             orders: VecDeque::new(),
             max_size: None,
+            journal: None,
         }
     }
 
@@ -25,10 +30,25 @@ impl OrderQueue {
         Self {
             orders: VecDeque::with_capacity(max_size),
             max_size: Some(max_size),
+            journal: None,
         }
     }
 
-    // Add an order to the queue
+    // Create a new order queue backed by the journal at the given path
+    // Commands recorded in the journal are restored into the queue.
+    pub fn with_journal(
+        path: impl AsRef<Path>,
+        compaction_threshold: usize,
+    ) -> Result<Self, JournalError> {
+        let (journal, commands) = Journal::open(path, compaction_threshold)?;
+        Ok(Self {
+            orders: commands.into_iter().map(Order::Command).collect(),
+            max_size: None,
+            journal: Some(journal),
+        })
+    }
+
+    // Add an order to the queue, commands are recorded in the journal
     pub fn add_order(&mut self, order: Order) -> Result<(), QueueError> {
         if let Some(max_size) = self.max_size {
             if self.orders.len() >= max_size {
@@ -36,7 +56,15 @@ impl OrderQueue {
             }
         }
 
+        // queued first, so a compaction triggered by the record keeps the command
+        let journaled = match &order {
+            Order::Command(command) => Some(command.clone()),
+            Order::Call(_) => None,
+        };
         self.orders.push_back(order);
+        if let Some(command) = journaled {
+            self.journal_write(|journal| journal.record_command_added(&command));
+        }
         Ok(())
     }
 
@@ -52,11 +80,15 @@ impl OrderQueue {
 
     // Remove an order by ID
     pub fn remove_order(&mut self, order_id: Uuid) -> Option<Order> {
-        if let Some(pos) = self.orders.iter().position(|order| order.id() == order_id) {
-            self.orders.remove(pos)
-        } else {
-            None
+        let pos = self
+            .orders
+            .iter()
+            .position(|order| order.id() == order_id)?;
+        let removed = self.orders.remove(pos);
+        if removed.as_ref().is_some_and(Order::is_command) {
+            self.journal_write(|journal| journal.record_order_removed(order_id));
         }
+        removed
     }
 
     // Get all orders (without removing them)
@@ -98,6 +130,10 @@ impl OrderQueue {
             self.orders.drain(..).partition(|order| order.is_expired());
 
         self.orders = valid;
+        for order in expired.iter().filter(|order| order.is_command()) {
+            let order_id = order.id();
+            self.journal_write(|journal| journal.record_order_removed(order_id));
+        }
         expired.into_iter().collect()
     }
 
@@ -135,6 +171,11 @@ impl OrderQueue {
     // Clear all orders
     pub fn clear(&mut self) {
         self.orders.clear();
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.compact(&[]) {
+                error!("Failed to clear order journal: {}", error);
+            }
+        }
     }
 
     // Get orders by floor
@@ -167,6 +208,31 @@ impl OrderQueue {
     pub fn get_newest_order(&self) -> Option<&Order> {
         self.orders.iter().max_by_key(|order| order.created_at())
     }
+
+    // Record a change in the journal, compacting it once it has grown large enough
+    // A failed write is logged rather than returned, the order is still served from memory.
+    fn journal_write<F>(&mut self, write: F)
+    where
+        F: FnOnce(&mut Journal) -> Result<(), JournalError>,
+    {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        if let Err(error) = write(journal) {
+            error!("Failed to write order journal: {}", error);
+            return;
+        }
+        if !journal.needs_compaction() {
+            return;
+        }
+
+        let commands = self.get_commands();
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.compact(&commands) {
+                error!("Failed to compact order journal: {}", error);
+            }
+        }
+    }
 }
 
 impl Default for OrderQueue {
@@ -198,7 +264,7 @@ mod tests {
     use super::*;
     use crate::clock::init_clock_with_random_id;
     use crate::queue::order::{Call, Command, Direction};
-    use crate::queue::scheduler::{FifoScheduler, SchedulerContext};
+    use crate::queue::scheduler::{NearestFloorScheduler, SchedulerContext};
 
     fn setup_test_clock() {
         let _ = init_clock_with_random_id();
@@ -266,8 +332,8 @@ mod tests {
         setup_test_clock();
 
         let mut queue = OrderQueue::new();
-        let context = SchedulerContext::new(1, Some(Direction::Up), Uuid::new_v4());
-        let scheduler = FifoScheduler;
+        let context = SchedulerContext::new(1, Some(Direction::Up), Uuid::new_v4(), 6);
+        let scheduler = NearestFloorScheduler;
 
         let call1 = Call::new(5, Direction::Up);
         let call2 = Call::new(2, Direction::Up);
@@ -338,4 +404,30 @@ mod tests {
         assert_eq!(newest.id(), call2.id);
         assert!(newest.created_at() > oldest.created_at());
     }
+
+    #[test]
+    fn test_journal_restores_commands() {
+        setup_test_clock();
+
+        let directory = std::env::temp_dir().join(format!("elevators-{}", Uuid::new_v4()));
+        let path = directory.join("cab_orders.journal");
+
+        let served = Command::new(1);
+        let pending = Command::new(4);
+        let added_as_order = Command::new(3);
+        {
+            let mut queue = OrderQueue::with_journal(&path, 100).unwrap();
+            queue.add_command(served.clone()).unwrap();
+            queue.add_command(pending.clone()).unwrap();
+            queue.add_order(added_as_order.clone().into()).unwrap();
+            queue.add_call(Call::new(2, Direction::Up)).unwrap();
+            queue.remove_order(served.id);
+        }
+
+        // hall calls are replicated by the peers, so only cab commands come back
+        let queue = OrderQueue::with_journal(&path, 100).unwrap();
+        assert_eq!(queue.get_commands(), vec![pending, added_as_order]);
+        assert_eq!(queue.count_calls(), 0);
+        let _ = std::fs::remove_dir_all(directory);
+    }
 }
@@ -1,24 +1,59 @@
-use super::order::{Direction, Order};
+use std::cmp::Ordering;
+use std::slice;
+use std::time::Duration;
+
+use super::assigner::{ElevatorStatus, HallCallAssigner, ESTIMATED_TRAVEL_TIME};
+use super::order::{Call, Direction, Order};
+use crate::elevator::ElevatorState;
+use uhlc::ID;
 use uuid::Uuid;
 
 // Context information for scheduling decisions
 #[derive(Debug, Clone)]
 pub struct SchedulerContext {
-    pub current_floor: u8,
+    pub current_floor: u8, // last floor reached
     pub current_direction: Option<Direction>,
     pub elevator_id: Uuid,
+    pub num_floors: u8,
+    pub is_door_open: bool,
+    pub is_obstructed: bool,
+    pub is_halted: bool,
+    pub is_between_floors: bool, // moving or stopped past current_floor
+    pub time_since_last_floor: Duration,
+    pub peers: Vec<PeerState>,
 }
 
 impl SchedulerContext {
-    pub fn new(current_floor: u8, current_direction: Option<Direction>, elevator_id: Uuid) -> Self {
+    // Context of a car standing at the floor with the door closed and no peers
+    pub fn new(
+        current_floor: u8,
+        current_direction: Option<Direction>,
+        elevator_id: Uuid,
+        num_floors: u8,
+    ) -> Self {
         Self {
             current_floor,
             current_direction,
             elevator_id,
+            num_floors,
+            is_door_open: false,
+            is_obstructed: false,
+            is_halted: false,
+            is_between_floors: false,
+            time_since_last_floor: Duration::ZERO,
+            peers: Vec::new(),
         }
     }
 }
 
+// What a peer elevator was last seen doing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerState {
+    pub node_id: ID,
+    pub is_available: bool,
+    pub status: ElevatorStatus,
+}
+
 // Trait for implementing different scheduling algorithms
 pub trait Scheduler {
     // Optimize the order of service for the given orders
@@ -29,6 +64,54 @@ pub trait Scheduler {
     fn name(&self) -> &'static str;
 }
 
+// Lets a scheduler chosen at runtime stand in wherever a scheduler is expected
+impl<S: Scheduler + ?Sized> Scheduler for Box<S> {
+    fn schedule(&self, orders: &[Order], context: &SchedulerContext) -> Vec<Order> {
+        (**self).schedule(orders, context)
+    }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+}
+
+// Builds a scheduler for an elevator with the number of floors, door open duration and the
+// time after which an obstructed door counts as stuck
+type SchedulerFactory = fn(u8, Duration, Duration) -> Box<dyn Scheduler + Send>;
+
+// Schedulers that can be chosen in the configuration, by the name they report
+const SCHEDULERS: [(&str, SchedulerFactory); 4] = [
+    ("fifo", |_, _, _| Box::new(FifoScheduler)),
+    ("collective", |_, _, _| Box::new(CollectiveScheduler)),
+    ("nearest", |_, _, _| Box::new(NearestFloorScheduler)),
+    (
+        "eta",
+        |num_floors, door_open_duration, door_stuck_timeout| {
+            Box::new(EtaScheduler::new(
+                HallCallAssigner::new(num_floors, ESTIMATED_TRAVEL_TIME, door_open_duration),
+                door_stuck_timeout,
+            ))
+        },
+    ),
+];
+
+pub fn scheduler_names() -> impl Iterator<Item = &'static str> {
+    SCHEDULERS.iter().map(|(name, _)| *name)
+}
+
+// Create the scheduler registered under the name, None if there is no such scheduler
+pub fn create_scheduler(
+    name: &str,
+    num_floors: u8,
+    door_open_duration: Duration,
+    door_stuck_timeout: Duration,
+) -> Option<Box<dyn Scheduler + Send>> {
+    SCHEDULERS
+        .iter()
+        .find(|(registered, _)| *registered == name)
+        .map(|(_, factory)| factory(num_floors, door_open_duration, door_stuck_timeout))
+}
+
 // Simple FIFO (First In, First Out) scheduler
 pub struct FifoScheduler;
 
@@ -41,7 +124,230 @@ impl Scheduler for FifoScheduler {
     }
 
     fn name(&self) -> &'static str {
-        "FIFO"
+        "fifo"
+    }
+}
+
+// Collective control, the LOOK algorithm of ordinary elevators
+// The car keeps travelling in its direction while anything remains ahead, stopping for cab
+// commands and for calls going its way. Once nothing is left ahead it turns around and serves
+// the calls going the other way, and the calls behind it in the original direction come last.
+pub struct CollectiveScheduler;
+
+impl CollectiveScheduler {
+    // Direction of the sweep, an idle car heads for its oldest order
+    fn sweep_direction(orders: &[Order], context: &SchedulerContext) -> Direction {
+        if let Some(direction) = context.current_direction {
+            return direction;
+        }
+        let Some(oldest) = orders.iter().min_by_key(|order| order.created_at()) else {
+            return Direction::Up;
+        };
+        match oldest.target_floor().cmp(&context.current_floor) {
+            Ordering::Greater => Direction::Up,
+            Ordering::Less => Direction::Down,
+            Ordering::Equal => oldest.direction().unwrap_or(Direction::Up),
+        }
+    }
+
+    // Sweep an order is served in, and its position within that sweep
+    fn sweep_position(order: &Order, floor: u8, direction: Direction) -> (u8, i16) {
+        // floors ahead in the sweep direction are positive
+        let ahead = match direction {
+            Direction::Up => order.target_floor() as i16 - floor as i16,
+            Direction::Down => floor as i16 - order.target_floor() as i16,
+        };
+        let is_going_back = order.direction().is_some_and(|call| call != direction);
+        if is_going_back || (ahead < 0 && order.is_command()) {
+            // on the way back, starting from the far end
+            (1, -ahead)
+        } else if ahead >= 0 {
+            (0, ahead)
+        } else {
+            // calls behind going our way, once the car has turned around again
+            (2, ahead)
+        }
+    }
+}
+
+impl Scheduler for CollectiveScheduler {
+    fn schedule(&self, orders: &[Order], context: &SchedulerContext) -> Vec<Order> {
+        let direction = Self::sweep_direction(orders, context);
+        let mut sorted_orders = orders.to_vec();
+        sorted_orders.sort_by_key(|order| {
+            (
+                Self::sweep_position(order, context.current_floor, direction),
+                order.created_at(),
+            )
+        });
+        sorted_orders
+    }
+
+    fn name(&self) -> &'static str {
+        "collective"
+    }
+}
+
+// Nearest floor first, the oldest order breaks ties
+// Greedy and blind to direction, so a steady stream of nearby orders can starve a far one.
+pub struct NearestFloorScheduler;
+
+impl Scheduler for NearestFloorScheduler {
+    fn schedule(&self, orders: &[Order], context: &SchedulerContext) -> Vec<Order> {
+        let mut sorted_orders = orders.to_vec();
+        sorted_orders.sort_by_key(|order| {
+            (
+                order.target_floor().abs_diff(context.current_floor),
+                order.created_at(),
+            )
+        });
+        sorted_orders
+    }
+
+    fn name(&self) -> &'static str {
+        "nearest"
+    }
+}
+
+// Estimated time of arrival, the order the car can serve the soonest goes first
+// Each order is timed from where the car stands after serving the previous one, so the car
+// works its way through nearby orders before travelling far, counting the door cycles. Hall
+// calls an available peer would reach sooner than this car come after everything else, which
+// is what happens to the calls of an obstructed or halted car.
+pub struct EtaScheduler {
+    assigner: HallCallAssigner,
+    door_stuck_timeout: Duration,
+}
+
+impl EtaScheduler {
+    pub fn new(assigner: HallCallAssigner, door_stuck_timeout: Duration) -> Self {
+        Self {
+            assigner,
+            door_stuck_timeout,
+        }
+    }
+
+    // What the car is doing as far as the estimate is concerned
+    fn behaviour(context: &SchedulerContext) -> ElevatorState {
+        if context.is_door_open {
+            ElevatorState::DoorOpen
+        } else if context.is_between_floors && context.current_direction.is_some() {
+            ElevatorState::Moving
+        } else {
+            ElevatorState::Idle
+        }
+    }
+
+    // Time to serve the order alone
+    fn eta(
+        &self,
+        order: &Order,
+        floor: u8,
+        direction: Option<Direction>,
+        behaviour: ElevatorState,
+    ) -> Duration {
+        let mut status = ElevatorStatus {
+            floor,
+            direction,
+            behaviour,
+            commands: Vec::new(),
+            calls: Vec::new(),
+        };
+        match order {
+            Order::Call(call) => status.calls.push(call.clone()),
+            Order::Command(command) => status.commands.push(command.clone()),
+        }
+        self.assigner.time_to_idle(&status, &[])
+    }
+
+    // Time for the car to serve the order alone from what it is doing now, MAX while halted
+    fn eta_now(&self, order: &Order, context: &SchedulerContext) -> Duration {
+        if context.is_halted {
+            return Duration::MAX;
+        }
+
+        let behaviour = Self::behaviour(context);
+        let mut eta = self.eta(
+            order,
+            context.current_floor,
+            context.current_direction,
+            behaviour,
+        );
+        if behaviour == ElevatorState::Moving {
+            // the estimate has the car halfway to the next floor, the clock knows better
+            let travel_time = self.assigner.travel_time();
+            eta = eta.saturating_sub(travel_time / 2)
+                + travel_time.saturating_sub(context.time_since_last_floor);
+        }
+        if context.is_door_open && context.is_obstructed {
+            eta += self.door_stuck_timeout;
+        }
+        eta
+    }
+
+    // Extra time the quickest available peer needs to serve the call, None without one
+    fn peer_eta(&self, call: &Call, context: &SchedulerContext) -> Option<Duration> {
+        context
+            .peers
+            .iter()
+            .filter(|peer| peer.is_available)
+            .map(|peer| {
+                let with_call = self
+                    .assigner
+                    .time_to_idle(&peer.status, slice::from_ref(call));
+                with_call.saturating_sub(self.assigner.time_to_idle(&peer.status, &[]))
+            })
+            .min()
+    }
+
+    // Whether an available peer would reach the order sooner than this car
+    fn is_served_sooner_by_peer(&self, order: &Order, context: &SchedulerContext) -> bool {
+        let Order::Call(call) = order else {
+            return false;
+        };
+        self.peer_eta(call, context)
+            .is_some_and(|peer_eta| peer_eta < self.eta_now(order, context))
+    }
+}
+
+impl Scheduler for EtaScheduler {
+    fn schedule(&self, orders: &[Order], context: &SchedulerContext) -> Vec<Order> {
+        let mut remaining: Vec<(bool, Order)> = orders
+            .iter()
+            .map(|order| (self.is_served_sooner_by_peer(order, context), order.clone()))
+            .collect();
+        let mut sorted_orders = Vec::with_capacity(orders.len());
+        let mut floor = context.current_floor;
+        let mut direction = context.current_direction;
+        // only the first order starts out from what the car is doing now
+        let mut behaviour = Self::behaviour(context);
+
+        while let Some(index) = (0..remaining.len()).min_by_key(|&index| {
+            let (is_deferred, order) = &remaining[index];
+            (
+                *is_deferred,
+                self.eta(order, floor, direction, behaviour),
+                order.created_at(),
+            )
+        }) {
+            let (_, order) = remaining.swap_remove(index);
+            // a call leaves the car heading its way, a cab command the way the car travelled
+            direction = order
+                .direction()
+                .or(match order.target_floor().cmp(&floor) {
+                    Ordering::Greater => Some(Direction::Up),
+                    Ordering::Less => Some(Direction::Down),
+                    Ordering::Equal => direction,
+                });
+            floor = order.target_floor();
+            behaviour = ElevatorState::Idle;
+            sorted_orders.push(order);
+        }
+        sorted_orders
+    }
+
+    fn name(&self) -> &'static str {
+        "eta"
     }
 }
 
@@ -49,7 +355,7 @@ impl Scheduler for FifoScheduler {
 mod tests {
     use super::*;
     use crate::clock::init_clock_with_random_id;
-    use crate::queue::order::{Call, Direction};
+    use crate::queue::order::{Call, Command, Direction};
 
     fn setup_test_clock() {
         let _ = init_clock_with_random_id();
@@ -59,7 +365,7 @@ mod tests {
     fn test_fifo_scheduler() {
         setup_test_clock();
 
-        let context = SchedulerContext::new(1, Some(Direction::Up), Uuid::new_v4());
+        let context = SchedulerContext::new(1, Some(Direction::Up), Uuid::new_v4(), 6);
         let scheduler = FifoScheduler;
 
         // Add orders with some delay to ensure different timestamps
@@ -74,4 +380,383 @@ mod tests {
         assert_eq!(scheduled[0].id(), call1.id);
         assert_eq!(scheduled[1].id(), call2.id);
     }
+
+    const DOOR_OPEN_DURATION: Duration = Duration::from_secs(3);
+
+    const DOOR_STUCK_TIMEOUT: Duration = Duration::from_secs(20);
+
+    fn eta_scheduler() -> EtaScheduler {
+        EtaScheduler::new(
+            HallCallAssigner::new(4, ESTIMATED_TRAVEL_TIME, DOOR_OPEN_DURATION),
+            DOOR_STUCK_TIMEOUT,
+        )
+    }
+
+    fn floors(orders: &[Order]) -> Vec<(u8, Option<Direction>)> {
+        orders
+            .iter()
+            .map(|order| (order.target_floor(), order.direction()))
+            .collect()
+    }
+
+    #[test]
+    fn test_collective_scheduler_sweeps_before_turning() {
+        setup_test_clock();
+
+        let orders: Vec<Order> = vec![
+            Call::new(0, Direction::Up).into(),
+            Command::new(3).into(),
+            Call::new(2, Direction::Down).into(),
+            Command::new(0).into(),
+            Call::new(2, Direction::Up).into(),
+        ];
+        let context = SchedulerContext::new(1, Some(Direction::Up), Uuid::new_v4(), 4);
+        let scheduled = CollectiveScheduler.schedule(&orders, &context);
+        assert_eq!(
+            floors(&scheduled),
+            vec![
+                (2, Some(Direction::Up)),
+                (3, None),
+                (2, Some(Direction::Down)),
+                (0, None),
+                (0, Some(Direction::Up)),
+            ]
+        );
+
+        // the same orders seen from a car going down
+        let context = SchedulerContext::new(2, Some(Direction::Down), Uuid::new_v4(), 4);
+        let scheduled = CollectiveScheduler.schedule(&orders, &context);
+        assert_eq!(
+            floors(&scheduled),
+            vec![
+                (2, Some(Direction::Down)),
+                (0, None),
+                (0, Some(Direction::Up)),
+                (2, Some(Direction::Up)),
+                (3, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collective_scheduler_idle_heads_for_oldest_order() {
+        setup_test_clock();
+
+        let orders: Vec<Order> = vec![
+            Command::new(0).into(),
+            Command::new(3).into(),
+            Command::new(1).into(),
+        ];
+        let context = SchedulerContext::new(2, None, Uuid::new_v4(), 4);
+        let scheduled = CollectiveScheduler.schedule(&orders, &context);
+        assert_eq!(floors(&scheduled), vec![(1, None), (0, None), (3, None)]);
+    }
+
+    #[test]
+    fn test_eta_scheduler_serves_nearby_orders_on_the_way() {
+        setup_test_clock();
+
+        let orders: Vec<Order> = vec![
+            Command::new(3).into(),
+            Call::new(0, Direction::Up).into(),
+            Command::new(2).into(),
+        ];
+        let context = SchedulerContext::new(1, None, Uuid::new_v4(), 4);
+        let scheduler = eta_scheduler();
+        let scheduled = scheduler.schedule(&orders, &context);
+        assert_eq!(
+            floors(&scheduled),
+            vec![(0, Some(Direction::Up)), (2, None), (3, None)]
+        );
+    }
+
+    #[test]
+    fn test_eta_scheduler_accounts_for_a_moving_car() {
+        setup_test_clock();
+
+        let orders: Vec<Order> = vec![Command::new(0).into(), Command::new(2).into()];
+        let scheduler = eta_scheduler();
+
+        // standing at the floor both are a floor away, the oldest goes first
+        let context = SchedulerContext::new(1, Some(Direction::Up), Uuid::new_v4(), 4);
+        let scheduled = scheduler.schedule(&orders, &context);
+        assert_eq!(floors(&scheduled), vec![(0, None), (2, None)]);
+
+        // already on the way up, turning back means passing floor 2 first
+        let context = SchedulerContext {
+            is_between_floors: true,
+            ..context
+        };
+        let scheduled = scheduler.schedule(&orders, &context);
+        assert_eq!(floors(&scheduled), vec![(2, None), (0, None)]);
+    }
+
+    fn peer_idle_at(floor: u8, is_available: bool) -> PeerState {
+        PeerState {
+            node_id: ID::try_from(0x0bu8).unwrap(),
+            is_available,
+            status: ElevatorStatus {
+                floor,
+                direction: None,
+                behaviour: ElevatorState::Idle,
+                commands: Vec::new(),
+                calls: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_eta_scheduler_leaves_calls_a_peer_reaches_sooner_for_last() {
+        setup_test_clock();
+
+        let orders: Vec<Order> = vec![Call::new(0, Direction::Up).into(), Command::new(3).into()];
+        let scheduler = eta_scheduler();
+        let context = SchedulerContext {
+            peers: vec![peer_idle_at(3, true)],
+            ..SchedulerContext::new(1, None, Uuid::new_v4(), 4)
+        };
+
+        // a free car is closer to the call than the peer at the top floor
+        let scheduled = scheduler.schedule(&orders, &context);
+        assert_eq!(
+            floors(&scheduled),
+            vec![(0, Some(Direction::Up)), (3, None)]
+        );
+
+        // obstructed for the door stuck time, the peer gets there sooner
+        let obstructed = SchedulerContext {
+            is_door_open: true,
+            is_obstructed: true,
+            ..context.clone()
+        };
+        let scheduled = scheduler.schedule(&orders, &obstructed);
+        assert_eq!(
+            floors(&scheduled),
+            vec![(3, None), (0, Some(Direction::Up))]
+        );
+
+        // a halted car never arrives, but an unavailable peer cannot take over either
+        let halted = SchedulerContext {
+            is_halted: true,
+            ..context.clone()
+        };
+        let scheduled = scheduler.schedule(&orders, &halted);
+        assert_eq!(
+            floors(&scheduled),
+            vec![(3, None), (0, Some(Direction::Up))]
+        );
+        let halted_alone = SchedulerContext {
+            peers: vec![peer_idle_at(3, false)],
+            ..halted
+        };
+        let scheduled = scheduler.schedule(&orders, &halted_alone);
+        assert_eq!(
+            floors(&scheduled),
+            vec![(0, Some(Direction::Up)), (3, None)]
+        );
+    }
+
+    #[test]
+    fn test_eta_scheduler_times_a_moving_car_by_the_clock() {
+        setup_test_clock();
+
+        let scheduler = eta_scheduler();
+        let order: Order = Command::new(3).into();
+        let context = SchedulerContext {
+            is_between_floors: true,
+            ..SchedulerContext::new(1, Some(Direction::Up), Uuid::new_v4(), 4)
+        };
+
+        let just_left = scheduler.eta_now(&order, &context);
+        let nearly_there = scheduler.eta_now(
+            &order,
+            &SchedulerContext {
+                time_since_last_floor: ESTIMATED_TRAVEL_TIME,
+                ..context
+            },
+        );
+        assert_eq!(just_left - nearly_there, ESTIMATED_TRAVEL_TIME);
+    }
+
+    #[test]
+    fn test_registry() {
+        for name in scheduler_names() {
+            let scheduler =
+                create_scheduler(name, 4, DOOR_OPEN_DURATION, DOOR_STUCK_TIMEOUT).unwrap();
+            assert_eq!(scheduler.name(), name);
+        }
+        assert_eq!(scheduler_names().count(), 4);
+        assert!(create_scheduler("elevator", 4, DOOR_OPEN_DURATION, DOOR_STUCK_TIMEOUT).is_none());
+    }
+
+    // Properties every scheduler must have, checked on random orders and contexts
+    mod conformance {
+        use super::*;
+        use proptest::prelude::*;
+
+        const NUM_FLOORS: u8 = 8;
+
+        // Every registered scheduler must pass
+        fn schedulers() -> Vec<Box<dyn Scheduler + Send>> {
+            scheduler_names()
+                .filter_map(|name| {
+                    create_scheduler(name, NUM_FLOORS, DOOR_OPEN_DURATION, DOOR_STUCK_TIMEOUT)
+                })
+                .collect()
+        }
+
+        fn direction() -> impl Strategy<Value = Direction> {
+            prop_oneof![Just(Direction::Up), Just(Direction::Down)]
+        }
+
+        // A call when there is a direction, a cab command otherwise
+        fn order_specs() -> impl Strategy<Value = Vec<(u8, Option<Direction>)>> {
+            prop::collection::vec((0..NUM_FLOORS, prop::option::of(direction())), 0..12)
+        }
+
+        // Floor and direction, the flags for door open, obstructed, halted and between floors,
+        // and the time since the last floor
+        type ContextSpec = (u8, Option<Direction>, [bool; 4], u64);
+
+        fn context() -> impl Strategy<Value = ContextSpec> {
+            (
+                0..NUM_FLOORS,
+                prop::option::of(direction()),
+                any::<[bool; 4]>(),
+                0..10_000u64,
+            )
+        }
+
+        // Orders are created in the test itself since they take their timestamp from the clock
+        fn build_orders(specs: &[(u8, Option<Direction>)]) -> Vec<Order> {
+            specs
+                .iter()
+                .map(|&(floor, direction)| match direction {
+                    Some(direction) => Call::new(floor, direction).into(),
+                    None => Command::new(floor).into(),
+                })
+                .collect()
+        }
+
+        fn build_context(spec: ContextSpec) -> SchedulerContext {
+            let (floor, direction, flags, milliseconds) = spec;
+            let [is_door_open, is_obstructed, is_halted, is_between_floors] = flags;
+            SchedulerContext {
+                is_door_open,
+                is_obstructed,
+                is_halted,
+                is_between_floors,
+                time_since_last_floor: Duration::from_millis(milliseconds),
+                ..SchedulerContext::new(floor, direction, Uuid::new_v4(), NUM_FLOORS)
+            }
+        }
+
+        fn ids(orders: &[Order]) -> Vec<Uuid> {
+            orders.iter().map(Order::id).collect()
+        }
+
+        fn check_conformance(
+            scheduler: &dyn Scheduler,
+            orders: &[Order],
+            context: &SchedulerContext,
+        ) -> Result<(), TestCaseError> {
+            let scheduled = scheduler.schedule(orders, context);
+
+            // every order exactly once, nothing made up
+            let mut expected = ids(orders);
+            let mut actual = ids(&scheduled);
+            expected.sort();
+            actual.sort();
+            prop_assert_eq!(
+                actual,
+                expected,
+                "{} lost or added orders",
+                scheduler.name()
+            );
+
+            // the orders are untouched, only their sequence changes
+            for order in &scheduled {
+                prop_assert!(orders.contains(order));
+            }
+
+            // the outcome depends on the orders, not on how they were handed over
+            let mut reversed = orders.to_vec();
+            reversed.reverse();
+            prop_assert_eq!(
+                ids(&scheduler.schedule(&reversed, context)),
+                ids(&scheduled),
+                "{} depends on the input order",
+                scheduler.name()
+            );
+            Ok(())
+        }
+
+        // Number of times the car changes direction serving the orders in sequence
+        fn reversals(floor: u8, orders: &[Order]) -> usize {
+            let mut position = floor;
+            let mut heading = None;
+            let mut count = 0;
+            for order in orders {
+                let next = match order.target_floor().cmp(&position) {
+                    Ordering::Greater => Some(Direction::Up),
+                    Ordering::Less => Some(Direction::Down),
+                    Ordering::Equal => None,
+                };
+                if let Some(next) = next {
+                    if heading.is_some_and(|heading| heading != next) {
+                        count += 1;
+                    }
+                    heading = Some(next);
+                }
+                position = order.target_floor();
+            }
+            count
+        }
+
+        proptest! {
+            #[test]
+            fn test_schedulers_conform(specs in order_specs(), context in context()) {
+                setup_test_clock();
+                let orders = build_orders(&specs);
+                let context = build_context(context);
+                for scheduler in schedulers() {
+                    check_conformance(scheduler.as_ref(), &orders, &context)?;
+                }
+            }
+
+            #[test]
+            fn test_fifo_serves_oldest_first(specs in order_specs(), context in context()) {
+                setup_test_clock();
+                let orders = build_orders(&specs);
+                let scheduled = FifoScheduler.schedule(&orders, &build_context(context));
+                for pair in scheduled.windows(2) {
+                    prop_assert!(pair[0].created_at() <= pair[1].created_at());
+                }
+            }
+
+            #[test]
+            fn test_nearest_serves_closest_first(specs in order_specs(), context in context()) {
+                setup_test_clock();
+                let orders = build_orders(&specs);
+                let context = build_context(context);
+                let scheduled = NearestFloorScheduler.schedule(&orders, &context);
+                let distance = |order: &Order| order.target_floor().abs_diff(context.current_floor);
+                for pair in scheduled.windows(2) {
+                    prop_assert!(distance(&pair[0]) <= distance(&pair[1]));
+                }
+            }
+
+            #[test]
+            fn test_collective_turns_around_at_most_twice(
+                specs in order_specs(),
+                context in context(),
+            ) {
+                setup_test_clock();
+                let orders = build_orders(&specs);
+                let context = build_context(context);
+                let scheduled = CollectiveScheduler.schedule(&orders, &context);
+                prop_assert!(reversals(context.current_floor, &scheduled) <= 2);
+            }
+        }
+    }
 }
@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crossbeam_channel as channel;
+use log::{error, info, warn};
+
+use crate::config::{self, Config, Tunables};
+
+// Watches the configuration files and hands tunable changes to the subscribers
+// The files are polled for a new modification time, as a change usually arrives as a
+// rewrite of the whole file. Changes to structural fields are ignored with a warning, as
+// the running threads were built around the old values and need a restart to pick them up.
+pub struct ConfigWatcher {
+    path: String,
+    profile: Option<String>,
+    running: Config,
+    tunables: Tunables,
+    overrides: Box<dyn Fn(&mut Config) + Send>, // command line values that always win
+    modified: Vec<Option<SystemTime>>,
+    poll_interval: Duration,
+    subscribers: Vec<channel::Sender<Tunables>>,
+    terminate_rx: channel::Receiver<()>,
+}
+
+impl ConfigWatcher {
+    pub fn new(
+        path: &str,
+        profile: Option<&str>,
+        running: Config,
+        overrides: Box<dyn Fn(&mut Config) + Send>,
+        poll_interval: Duration,
+        subscribers: Vec<channel::Sender<Tunables>>,
+        terminate_rx: channel::Receiver<()>,
+    ) -> ConfigWatcher {
+        let mut watcher = ConfigWatcher {
+            path: path.to_string(),
+            profile: profile.map(str::to_string),
+            tunables: running.tunables(),
+            running,
+            overrides,
+            modified: Vec::new(),
+            poll_interval,
+            subscribers,
+            terminate_rx,
+        };
+        watcher.modified = watcher.modification_times();
+        watcher
+    }
+
+    pub fn run(mut self) {
+        info!("Watching {} for configuration changes", self.path);
+
+        loop {
+            channel::select! {
+              recv(self.terminate_rx) -> _ => {
+                break;
+              }
+              default(self.poll_interval) => {
+                let modified = self.modification_times();
+                if modified != self.modified {
+                    self.modified = modified;
+                    self.reload();
+                }
+              }
+            }
+        }
+    }
+
+    // Load the files again and publish the tunables if they changed
+    fn reload(&mut self) {
+        let mut reloaded = match config::load(&self.path, self.profile.as_deref()) {
+            Ok(config) => config,
+            Err(error) => {
+                error!(
+                    "Keeping the current configuration, reload failed: {}",
+                    error
+                );
+                return;
+            }
+        };
+        (self.overrides)(&mut reloaded);
+
+        if let Some(tunables) = self.apply(&reloaded) {
+            info!("Configuration reloaded: {:?}", tunables);
+            self.subscribers
+                .retain(|subscriber| subscriber.send(tunables.clone()).is_ok());
+        }
+    }
+
+    // Take over the tunables of a reloaded configuration, returns them if any changed
+    fn apply(&mut self, reloaded: &Config) -> Option<Tunables> {
+        let ignored = self.running.structural_changes(reloaded);
+        if !ignored.is_empty() {
+            warn!(
+                "Ignoring changes to {} in reloaded configuration, a restart is required",
+                ignored.join(", ")
+            );
+        }
+
+        let tunables = reloaded.tunables();
+        if tunables == self.tunables {
+            return None;
+        }
+        self.tunables = tunables.clone();
+        Some(tunables)
+    }
+
+    fn modification_times(&self) -> Vec<Option<SystemTime>> {
+        let mut paths = vec![PathBuf::from(&self.path)];
+        if let Some(profile) = &self.profile {
+            paths.push(Path::new(&self.path).with_file_name(format!("config-{}.toml", profile)));
+        }
+
+        paths
+            .iter()
+            .map(|path| {
+                fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn setup_watcher(path: &Path) -> (ConfigWatcher, channel::Receiver<Tunables>) {
+        let (subscriber_tx, subscriber_rx) = channel::unbounded();
+        let (_, terminate_rx) = channel::unbounded();
+        let watcher = ConfigWatcher::new(
+            &path.to_string_lossy(),
+            None,
+            Config::default(),
+            Box::new(|_| {}),
+            Duration::from_millis(10),
+            vec![subscriber_tx],
+            terminate_rx,
+        );
+        (watcher, subscriber_rx)
+    }
+
+    fn temporary_config(contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("elevators-{}.toml", Uuid::new_v4()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_tunable_changes_are_published() {
+        let path = temporary_config("");
+        let (mut watcher, subscriber_rx) = setup_watcher(&path);
+
+        fs::write(
+            &path,
+            "[hardware]\ndoor_open_duration_milliseconds = 5000\norder_expiry_seconds = 90\n",
+        )
+        .unwrap();
+        watcher.reload();
+
+        let tunables = subscriber_rx.try_recv().unwrap();
+        assert_eq!(tunables.door_open_duration_milliseconds, 5000);
+        assert_eq!(tunables.order_expiry_seconds, 90);
+
+        // reloading the same values does not bother the subscribers
+        watcher.reload();
+        assert!(subscriber_rx.try_recv().is_err());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_structural_changes_are_ignored() {
+        let path = temporary_config("");
+        let (mut watcher, subscriber_rx) = setup_watcher(&path);
+
+        fs::write(&path, "[hardware]\nnum_floors = 6\n").unwrap();
+        watcher.reload();
+        assert!(subscriber_rx.try_recv().is_err());
+        assert_eq!(watcher.running.hardware.num_floors, 4);
+
+        fs::write(
+            &path,
+            "[hardware]\nnum_floors = 6\ndriver_channel_poll_timeout_milliseconds = 25\n",
+        )
+        .unwrap();
+        watcher.reload();
+        assert_eq!(
+            subscriber_rx
+                .try_recv()
+                .unwrap()
+                .driver_channel_poll_timeout_milliseconds,
+            25
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_invalid_reload_keeps_configuration() {
+        let path = temporary_config("");
+        let (mut watcher, subscriber_rx) = setup_watcher(&path);
+
+        fs::write(&path, "[hardware]\ndoor_open_duration_milliseconds = 0\n").unwrap();
+        watcher.reload();
+        assert!(subscriber_rx.try_recv().is_err());
+        assert_eq!(watcher.tunables, Config::default().tunables());
+        let _ = fs::remove_file(path);
+    }
+}
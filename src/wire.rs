@@ -0,0 +1,196 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Version of the wire format written by this build
+// Bump this whenever a serialized type changes shape, and teach decode_payload to upgrade
+// the previous version so mixed-version nodes keep understanding each other.
+pub const WIRE_VERSION: u8 = 1;
+
+#[derive(Serialize)]
+struct JsonEnvelope<'a, T> {
+    version: u8,
+    payload: &'a T,
+}
+
+#[derive(Deserialize)]
+struct RawJsonEnvelope {
+    version: u8,
+    payload: serde_json::Value,
+}
+
+// Encode a value in the compact binary format, prefixed by the version byte
+pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, WireError> {
+    let mut bytes = vec![WIRE_VERSION];
+    bincode::serialize_into(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+// Decode a value from the compact binary format
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, WireError> {
+    let (&version, payload) = bytes.split_first().ok_or(WireError::Empty)?;
+    decode_payload(version, payload)
+}
+
+// Encode a value as human readable JSON, for debugging and logs
+pub fn encode_json<T: Serialize>(value: &T) -> Result<String, WireError> {
+    let envelope = JsonEnvelope {
+        version: WIRE_VERSION,
+        payload: value,
+    };
+    Ok(serde_json::to_string(&envelope)?)
+}
+
+// Decode a value from the JSON debug format
+pub fn decode_json<T: DeserializeOwned>(json: &str) -> Result<T, WireError> {
+    let envelope: RawJsonEnvelope = serde_json::from_str(json)?;
+    match envelope.version {
+        WIRE_VERSION => Ok(serde_json::from_value(envelope.payload)?),
+        version => Err(WireError::UnsupportedVersion(version)),
+    }
+}
+
+fn decode_payload<T: DeserializeOwned>(version: u8, payload: &[u8]) -> Result<T, WireError> {
+    match version {
+        WIRE_VERSION => Ok(bincode::deserialize(payload)?),
+        // upgrades from older versions go here once the format changes
+        _ => Err(WireError::UnsupportedVersion(version)),
+    }
+}
+
+// Errors that can occur when encoding or decoding wire messages
+#[derive(Debug)]
+pub enum WireError {
+    Empty,
+    UnsupportedVersion(u8),
+    Binary(bincode::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Empty => write!(f, "Message is empty"),
+            WireError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported wire version {} (this node speaks {})",
+                version, WIRE_VERSION
+            ),
+            WireError::Binary(error) => write!(f, "Binary encoding error: {}", error),
+            WireError::Json(error) => write!(f, "JSON encoding error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+impl From<bincode::Error> for WireError {
+    fn from(error: bincode::Error) -> Self {
+        WireError::Binary(error)
+    }
+}
+
+impl From<serde_json::Error> for WireError {
+    fn from(error: serde_json::Error) -> Self {
+        WireError::Json(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::init_clock_with_random_id;
+    use crate::queue::order::{Call, Command, Direction, Order};
+    use std::fmt::Debug;
+    use uuid::Uuid;
+
+    fn setup_test_clock() {
+        let _ = init_clock_with_random_id();
+    }
+
+    fn assert_round_trip<T>(value: &T)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let bytes = encode(value).unwrap();
+        assert_eq!(bytes[0], WIRE_VERSION);
+        assert_eq!(&decode::<T>(&bytes).unwrap(), value);
+
+        let json = encode_json(value).unwrap();
+        assert_eq!(&decode_json::<T>(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_direction_round_trip() {
+        assert_round_trip(&Direction::Up);
+        assert_round_trip(&Direction::Down);
+    }
+
+    #[test]
+    fn test_call_round_trip() {
+        setup_test_clock();
+
+        assert_round_trip(&Call::new(3, Direction::Up));
+        assert_round_trip(&Call::new_with_expiration(0, Direction::Down, 5));
+    }
+
+    #[test]
+    fn test_command_round_trip() {
+        setup_test_clock();
+
+        let mut command = Command::new(2);
+        assert_round_trip(&command);
+
+        command.claim(Uuid::new_v4());
+        assert_round_trip(&command);
+    }
+
+    #[test]
+    fn test_order_round_trip() {
+        setup_test_clock();
+
+        assert_round_trip(&Order::from(Call::new(1, Direction::Down)));
+        assert_round_trip(&Order::from(Command::new_with_expiration(4, 120)));
+    }
+
+    #[test]
+    fn test_json_is_readable() {
+        setup_test_clock();
+
+        let json = encode_json(&Order::from(Call::new(1, Direction::Up))).unwrap();
+        assert!(json.starts_with(&format!("{{\"version\":{}", WIRE_VERSION)));
+        assert!(json.contains("\"target_floor\":1"));
+        assert!(json.contains("\"Up\""));
+    }
+
+    #[test]
+    fn test_unsupported_version_is_rejected() {
+        setup_test_clock();
+
+        let mut bytes = encode(&Command::new(1)).unwrap();
+        bytes[0] = WIRE_VERSION + 1;
+        assert!(matches!(
+            decode::<Command>(&bytes),
+            Err(WireError::UnsupportedVersion(version)) if version == WIRE_VERSION + 1
+        ));
+
+        let json = format!(r#"{{"version":{},"payload":"Up"}}"#, WIRE_VERSION + 1);
+        assert!(matches!(
+            decode_json::<Direction>(&json),
+            Err(WireError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_malformed_input_is_rejected() {
+        assert!(matches!(decode::<Direction>(&[]), Err(WireError::Empty)));
+        assert!(matches!(
+            decode::<Direction>(&[WIRE_VERSION, 0xff]),
+            Err(WireError::Binary(_))
+        ));
+        assert!(matches!(
+            decode_json::<Direction>("not json"),
+            Err(WireError::Json(_))
+        ));
+    }
+}